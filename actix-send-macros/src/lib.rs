@@ -14,7 +14,12 @@ use syn::{
     Signature, Stmt, Type, TypePath, Variant, VisPublic, Visibility,
 };
 
-use crate::message::{ActorInfo, HandleMethodInfo};
+use crate::message::{
+    always_run_method, bind_context_stmt, context_arg_ident, context_fn_arg,
+    detached_handle_method, handle_method_name, is_err_method, map_is_err_method,
+    message_name_method, priority_method, retry_copy_method, route_key_method, ActorInfo,
+    HandleMethodInfo, MessageAttr,
+};
 use quote::quote;
 
 mod message;
@@ -210,7 +215,7 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
             let (_, items) = mod_item.content.as_mut().expect("mod is empty");
 
             // We will throw away all struct that have message attribute and collect some info.
-            let mut message_params: Vec<(Ident, Generics, Type, bool)> = Vec::new();
+            let mut message_params: Vec<(Ident, Generics, MessageAttr)> = Vec::new();
             // We collect attributes separately as they would apply to the final enum.
             let mut attributes: Vec<Attribute> = Vec::new();
             // We extract the actor's ident string and use it generate message enum struct ident.
@@ -222,44 +227,17 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                         // before we throw them we collect all the type, field and message's return type
                         // attributes other than message are collected as well.
                         if let Some(attr) = is_ident(&struct_item.attrs, "message") {
-                            let mut test: String = attr
-                                .tokens
-                                .to_string()
-                                .split('=')
-                                .collect::<Vec<&str>>()
-                                .pop()
-                                .expect("#[message(result = \"T\")] is missing")
-                                .chars()
-                                .filter(|char| char != &'\"' && char != &' ')
-                                .collect();
-
-                            test.pop();
-
-                            let is_blocking = test.contains("blocking");
-
-                            if is_blocking {
-                                for _i in 0..9 {
-                                    test.pop();
-                                }
-                            }
-
-                            let result_typ =
-                                syn::parse_str::<syn::Type>(&test).unwrap_or_else(|_| {
-                                    panic!("Failed parsing string: {} to type", test)
-                                });
-
                             message_params.push((
                                 struct_item.ident.clone(),
                                 struct_item.generics.clone(),
-                                result_typ,
-                                is_blocking,
+                                MessageAttr::parse(attr),
                             ));
 
                             // ToDo: We are doing extra work here and collect the message attribute too.
                             attributes.extend(struct_item.attrs.iter().cloned());
 
                             // remove all attribute for message type.
-                            (*struct_item).attrs = vec![];
+                            struct_item.attrs = vec![];
                         }
 
                         if let Some(_attr) = is_ident(&struct_item.attrs, "actor") {
@@ -270,44 +248,17 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                         // before we throw them we collect all the type, field and message's return type
                         // attributes other than message are collected as well.
                         if let Some(attr) = is_ident(&type_item.attrs, "message") {
-                            let mut test: String = attr
-                                .tokens
-                                .to_string()
-                                .split('=')
-                                .collect::<Vec<&str>>()
-                                .pop()
-                                .expect("#[message(result = \"T\")] is missing")
-                                .chars()
-                                .filter(|char| char != &'\"' && char != &' ')
-                                .collect();
-
-                            test.pop();
-
-                            let is_blocking = test.contains("blocking");
-
-                            if is_blocking {
-                                for _i in 0..9 {
-                                    test.pop();
-                                }
-                            }
-
-                            let result_typ =
-                                syn::parse_str::<syn::Type>(&test).unwrap_or_else(|_| {
-                                    panic!("Failed parsing string: {} to type", test)
-                                });
-
                             message_params.push((
                                 type_item.ident.clone(),
                                 type_item.generics.clone(),
-                                result_typ,
-                                is_blocking,
+                                MessageAttr::parse(attr),
                             ));
 
                             // ToDo: We are doing extra work here and collect the message attribute too.
                             attributes.extend(type_item.attrs.iter().cloned());
 
                             // remove all attribute for message type.
-                            (*type_item).attrs = vec![];
+                            type_item.attrs = vec![];
                         }
 
                         if let Some(_attr) = is_ident(&type_item.attrs, "actor") {
@@ -319,19 +270,16 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
             }
 
             // remove all message attributes
-            attributes = attributes
-                .into_iter()
-                .filter(|attr| {
-                    attr.path
-                        .segments
-                        .first()
-                        .map(|seg| {
-                            let PathSegment { ident, .. } = seg;
-                            ident.to_string().as_str() != "message"
-                        })
-                        .unwrap_or(true)
-                })
-                .collect();
+            attributes.retain(|attr| {
+                attr.path
+                    .segments
+                    .first()
+                    .map(|seg| {
+                        let PathSegment { ident, .. } = seg;
+                        ident.to_string().as_str() != "message"
+                    })
+                    .unwrap_or(true)
+            });
 
             let message_enum_ident =
                 Ident::new(&format!("{}Message", actor_ident_str), Span::call_site());
@@ -370,9 +318,17 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                 Type::Path(type_path_from_idents(vec![message_enum_ident.clone()]));
 
             // ToDo: for now we ignore all generic params for message.
-            for (message_ident, _generics, result_type, is_blocking) in
-                message_params.iter().cloned()
-            {
+            for (message_ident, _generics, message_attr) in message_params.iter().cloned() {
+                let MessageAttr {
+                    result: result_type,
+                    is_blocking,
+                    is_fallible,
+                    is_high_priority,
+                    has_route_key,
+                    is_idempotent,
+                    ..
+                } = message_attr;
+
                 // construct a message's type path firstly we would use it multiple times later
                 let message_type_path = type_path_from_idents(vec![message_ident.clone()]);
 
@@ -714,7 +670,13 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                     trait_: Some((None, path, Default::default())),
                     self_ty: Box::new(Type::Path(message_type_path.clone())),
                     brace_token: Default::default(),
-                    items: vec![ImplItem::Type(impl_type), ImplItem::Method(method)],
+                    items: vec![ImplItem::Type(impl_type), ImplItem::Method(method)]
+                        .into_iter()
+                        .chain(priority_method(is_high_priority))
+                        .chain(route_key_method(has_route_key))
+                        .chain(retry_copy_method(is_idempotent))
+                        .chain(map_is_err_method(is_fallible))
+                        .collect(),
                 });
 
                 items.push(impl_item);
@@ -766,9 +728,16 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                         .expect("handle method must have a legit TypePath for Message type")
                         .expect("handle method must have a argument as msg: MessageType");
 
-                    (arg_ident, ident.clone(), method.block.stmts.clone())
+                    let context_ident = context_arg_ident(&method.sig.inputs);
+
+                    (
+                        arg_ident,
+                        ident.clone(),
+                        method.block.stmts.clone(),
+                        context_ident,
+                    )
                 })
-                .collect::<Vec<(Ident, Ident, Vec<Stmt>)>>();
+                .collect::<Vec<(Ident, Ident, Vec<Stmt>, Option<Ident>)>>();
 
             // ToDo: We are doing extra work removing all the #[handler] impls
             *items = items
@@ -807,21 +776,52 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                 ty: Box::new(message_enum_type),
             }));
 
+            // Handler::handle_with_context is generated instead when any handle method takes the
+            // Context as argument.
+            let with_context = handle_methods.iter().any(|(_, _, _, ctx)| ctx.is_some());
+            if with_context {
+                inputs.push(context_fn_arg());
+            }
+
             let mut path = Path {
                 leading_colon: None,
                 segments: Default::default(),
             };
 
             path.segments.push(PathSegment {
-                ident: message_enum_ident,
+                ident: message_enum_ident.clone(),
                 arguments: Default::default(),
             });
 
             // We just throw the statements of handle method for every type of message into the final handle method's enum variants.
 
+            // Handler::is_err would check the result of fallible messages.
+            let is_err = is_err_method(
+                &result_enum_ident,
+                message_params
+                    .iter()
+                    .filter(|(_, _, attr)| attr.is_fallible)
+                    .map(|(ident, _, attr)| (ident, attr.is_blocking)),
+            );
+
+            // Handler::always_run would check the messages marked as always_run.
+            let always_run = always_run_method(
+                &message_enum_ident,
+                message_params
+                    .iter()
+                    .filter(|(_, _, attr)| attr.is_always_run)
+                    .map(|(ident, _, _)| ident),
+            );
+
+            // Handler::message_name would return the variant name of message.
+            let message_name = message_name_method(
+                &message_enum_ident,
+                message_params.iter().map(|(ident, _, _)| ident),
+            );
+
             let arms = message_params
                 .into_iter()
-                .map(|(message_ident, _, _, is_blocking)| {
+                .map(|(message_ident, _, MessageAttr { is_blocking, .. })| {
                     let mut path = path.clone();
 
                     path.segments.push(PathSegment {
@@ -837,7 +837,7 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
 
                     let ident = handle_methods
                         .iter()
-                        .find_map(|(arg_ident, msg_ident, _)| {
+                        .find_map(|(arg_ident, msg_ident, _, _)| {
                             if msg_ident == &message_ident {
                                 Some(arg_ident.clone())
                             } else {
@@ -861,9 +861,13 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
 
                     let stmts = handle_methods
                         .iter()
-                        .find_map(|(_, ident, stmts)| {
+                        .find_map(|(_, ident, stmts, context_ident)| {
                             if ident == &message_ident {
-                                Some(stmts.clone())
+                                let mut stmts = stmts.clone();
+                                if let Some(context_ident) = context_ident {
+                                    stmts.insert(0, bind_context_stmt(context_ident, is_blocking));
+                                }
+                                Some(stmts)
                             } else {
                                 None
                             }
@@ -1005,7 +1009,7 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                 })
                 .collect();
 
-            let handle = Item::Impl(ItemImpl {
+            let mut handle = ItemImpl {
                 attrs: vec![attr_from_ident_str(vec!["handler"])],
                 defaultness: None,
                 unsafety: None,
@@ -1028,14 +1032,16 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                         unsafety: None,
                         abi: None,
                         fn_token: Default::default(),
-                        ident: Ident::new("handle", Span::call_site()),
+                        ident: Ident::new(handle_method_name(with_context), Span::call_site()),
                         generics: Default::default(),
                         paren_token: Default::default(),
                         inputs,
                         variadic: None,
                         output: ReturnType::Type(
                             Default::default(),
-                            Box::new(Type::Path(type_path_from_idents(vec![result_enum_ident]))),
+                            Box::new(Type::Path(type_path_from_idents(vec![
+                                result_enum_ident.clone()
+                            ]))),
                         ),
                     },
                     block: Block {
@@ -1053,9 +1059,19 @@ pub fn actor_mod(_meta: TokenStream, input: TokenStream) -> TokenStream {
                         }))],
                     },
                 })],
-            });
+            };
+
+            if with_context {
+                handle.items.push(detached_handle_method(
+                    &message_enum_ident,
+                    &result_enum_ident,
+                ));
+            }
+            handle.items.extend(is_err);
+            handle.items.extend(always_run);
+            handle.items.extend(message_name);
 
-            items.push(handle);
+            items.push(Item::Impl(handle));
 
             let expand = quote! {
                 #mod_item
@@ -1214,7 +1230,7 @@ pub fn handler_v2(meta: TokenStream, input: TokenStream) -> TokenStream {
 
             actor_info
                 .enum_variants(&handle_info)
-                .from_trait(&handle_info)
+                .impl_from_trait(&handle_info)
                 .map_result_trait(&handle_info)
                 .handler_trait(&handle_info, no_send);
             // .send_method_wrapper(&handle_info);
@@ -1,16 +1,306 @@
 use proc_macro2::Span;
 use quote::quote;
 use syn::{
-    punctuated::Punctuated, token::Paren, AngleBracketedGenericArguments, Arm, Block, Expr,
-    ExprAsync, ExprAwait, ExprBlock, ExprCall, ExprClosure, ExprMacro, ExprMatch, ExprPath, Field,
-    Fields, FieldsUnnamed, FnArg, GenericArgument, Ident, ImplItem, ImplItemMethod, ImplItemType,
-    Item, ItemEnum, ItemImpl, Local, Macro, MacroDelimiter, ParenthesizedGenericArguments, Pat,
-    PatIdent, PatTuple, PatTupleStruct, PatType, PatWild, Path, PathArguments, PathSegment,
-    Receiver, ReturnType, Signature, Stmt, Type, TypePath, TypeTuple, Variant, VisPublic,
-    Visibility,
+    parse_quote, punctuated::Punctuated, token::Paren, AngleBracketedGenericArguments, Arm,
+    Attribute, Block, Expr, ExprAsync, ExprAwait, ExprBlock, ExprCall, ExprClosure, ExprMacro,
+    ExprMatch, ExprPath, Field, Fields, FieldsUnnamed, FnArg, GenericArgument, Ident, ImplItem,
+    ImplItemMethod, ImplItemType, Item, ItemEnum, ItemImpl, Lit, Local, Macro, MacroDelimiter,
+    Meta, MetaNameValue, NestedMeta, ParenthesizedGenericArguments, Pat, PatIdent, PatTuple,
+    PatTupleStruct, PatType, PatWild, Path, PathArguments, PathSegment, Receiver, ReturnType,
+    Signature, Stmt, Type, TypePath, TypeTuple, Variant, VisPublic, Visibility,
 };
 
-use crate::{attr_from_ident_str, path_from_ident_str, type_path_from_idents};
+use crate::{attr_from_ident_str, is_ident, path_from_ident_str, type_path_from_idents};
+
+// Info collected from
+// #[message(
+//     result = "T", blocking, fallible, always_run, priority = "high", route_key, idempotent
+// )] attribute.
+#[derive(Clone)]
+pub(crate) struct MessageAttr {
+    pub(crate) result: Type,
+    pub(crate) is_blocking: bool,
+    pub(crate) is_fallible: bool,
+    pub(crate) is_always_run: bool,
+    pub(crate) is_high_priority: bool,
+    pub(crate) has_route_key: bool,
+    pub(crate) is_idempotent: bool,
+}
+
+impl MessageAttr {
+    pub(crate) fn parse(attr: &Attribute) -> Self {
+        let mut result = None;
+        let mut is_blocking = false;
+        let mut is_fallible = false;
+        let mut is_always_run = false;
+        let mut is_high_priority = false;
+        let mut has_route_key = false;
+        let mut is_idempotent = false;
+
+        let nested = match attr.parse_meta() {
+            Ok(Meta::List(list)) => list.nested,
+            _ => panic!("#[message(result = \"T\")] is missing"),
+        };
+
+        for meta in nested.iter() {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("result") => {
+                    let ty = lit.value();
+                    let typ = syn::parse_str::<Type>(&ty)
+                        .unwrap_or_else(|_| panic!("Failed parsing string: {} to type", ty));
+                    result = Some(typ);
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("priority") => is_high_priority = is_high(&lit.value()),
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("blocking") => {
+                    is_blocking = true
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("fallible") => {
+                    is_fallible = true
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("always_run") => {
+                    is_always_run = true
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("route_key") => {
+                    has_route_key = true
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("idempotent") => {
+                    is_idempotent = true
+                }
+                _ => panic!("Unknown argument for #[message] attribute"),
+            }
+        }
+
+        Self {
+            result: result.expect("#[message(result = \"T\")] is missing"),
+            is_blocking,
+            is_fallible,
+            is_always_run,
+            is_high_priority,
+            has_route_key,
+            is_idempotent,
+        }
+    }
+}
+
+// parse the value of priority attribute.
+pub(crate) fn is_high(priority: &str) -> bool {
+    match priority {
+        "high" => true,
+        "normal" => false,
+        _ => panic!(
+            "Unknown priority: {}. Expect \"high\" or \"normal\"",
+            priority
+        ),
+    }
+}
+
+// Generate MapResult::is_err method for fallible message.
+// Return None if the message is not fallible and the default method would be used.
+pub(crate) fn map_is_err_method(is_fallible: bool) -> Option<ImplItem> {
+    if !is_fallible {
+        return None;
+    }
+
+    Some(parse_quote! {
+        fn is_err(output: &Self::Output) -> bool {
+            FallibleResult::is_err(output)
+        }
+    })
+}
+
+// The `_ctx: &mut Context<Self>` argument of generated Handler::handle method.
+pub(crate) fn context_fn_arg() -> FnArg {
+    parse_quote! { _ctx: &mut actix_send::prelude::Context<Self> }
+}
+
+// The name of generated Handler method. Handler::handle_with_context is generated when any handle
+// method takes the Context as argument.
+pub(crate) fn handle_method_name(with_context: bool) -> &'static str {
+    if with_context {
+        "handle_with_context"
+    } else {
+        "handle"
+    }
+}
+
+// Handler::handle is still required when Handler::handle_with_context is generated.
+// Actor instances always call handle_with_context so this method is only used when handling a
+// message directly and it passes a detached Context.
+pub(crate) fn detached_handle_method(message: &Ident, result: &Ident) -> ImplItem {
+    parse_quote! {
+        async fn handle(&mut self, msg: #message) -> #result {
+            let mut ctx = actix_send::prelude::Context::detached();
+            self.handle_with_context(msg, &mut ctx).await
+        }
+    }
+}
+
+// Find the ident of `ctx: &mut Context<Self>` argument of a handle method.
+pub(crate) fn context_arg_ident<'a>(inputs: impl IntoIterator<Item = &'a FnArg>) -> Option<Ident> {
+    inputs.into_iter().find_map(|arg| {
+        let PatType { pat, ty, .. } = match arg {
+            FnArg::Typed(pat) => pat,
+            _ => return None,
+        };
+
+        let path = match ty.as_ref() {
+            Type::Reference(reference) => match reference.elem.as_ref() {
+                Type::Path(path) => path,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        if path.path.segments.last()?.ident != "Context" {
+            return None;
+        }
+
+        match pat.as_ref() {
+            Pat::Ident(ident) => Some(ident.ident.clone()),
+            _ => Some(Ident::new("_", Span::call_site())),
+        }
+    })
+}
+
+// Bind the context argument of generated handle method to the ident used by handle method.
+// Blocking handle methods run on a blocking thread and can't borrow the context.
+pub(crate) fn bind_context_stmt(ident: &Ident, is_blocking: bool) -> Stmt {
+    if is_blocking {
+        panic!("Blocking handle method can not take Context as argument");
+    }
+    parse_quote! { let #ident = _ctx; }
+}
+
+// Generate MapResult::priority method for high priority message.
+// Return None for normal priority and the default method would be used.
+pub(crate) fn priority_method(is_high_priority: bool) -> Option<ImplItem> {
+    if !is_high_priority {
+        return None;
+    }
+
+    Some(parse_quote! {
+        fn priority() -> Priority {
+            Priority::High
+        }
+    })
+}
+
+// Generate MapResult::route_key method for message implementing RouteKey trait.
+// Return None if the message has no route key and the default method would be used.
+pub(crate) fn route_key_method(has_route_key: bool) -> Option<ImplItem> {
+    if !has_route_key {
+        return None;
+    }
+
+    Some(parse_quote! {
+        fn route_key(&self) -> Option<u64> {
+            Some(actix_send::hash_route_key(
+                actix_send::prelude::RouteKey::route_key(self),
+            ))
+        }
+    })
+}
+
+// Generate MapResult::retry_copy method for message implementing Idempotent trait.
+// Return None if the message is not idempotent and the default method would be used.
+pub(crate) fn retry_copy_method(is_idempotent: bool) -> Option<ImplItem> {
+    if !is_idempotent {
+        return None;
+    }
+
+    Some(parse_quote! {
+        fn retry_copy(&self) -> Option<Self> {
+            Some(actix_send::copy_idempotent(self))
+        }
+    })
+}
+
+// Generate Handler::always_run method for messages that are handled even when the caller is gone.
+// Return None if there is no such message and the default method would be used.
+pub(crate) fn always_run_method<'a>(
+    message_enum_ident: &Ident,
+    always_run: impl Iterator<Item = &'a Ident>,
+) -> Option<ImplItem> {
+    let pats = always_run
+        .map(|message_ident| quote! { #message_enum_ident::#message_ident(_) })
+        .collect::<Vec<_>>();
+
+    if pats.is_empty() {
+        return None;
+    }
+
+    Some(parse_quote! {
+        fn always_run(msg: &Self::Message) -> bool {
+            matches!(msg, #(#pats)|*)
+        }
+    })
+}
+
+// Generate Handler::message_name method returning the variant name of message enum.
+pub(crate) fn message_name_method<'a>(
+    message_enum_ident: &Ident,
+    messages: impl Iterator<Item = &'a Ident>,
+) -> Option<ImplItem> {
+    let arms = messages
+        .map(|message_ident| {
+            let name = message_ident.to_string();
+            quote! { #message_enum_ident::#message_ident(_) => #name, }
+        })
+        .collect::<Vec<_>>();
+
+    if arms.is_empty() {
+        return None;
+    }
+
+    Some(parse_quote! {
+        fn message_name(msg: &Self::Message) -> &'static str {
+            match msg {
+                #(#arms)*
+            }
+        }
+    })
+}
+
+// Generate Handler::is_err method for fallible messages' (ident, is_blocking).
+// Return None if there is no fallible message and the default method would be used.
+pub(crate) fn is_err_method<'a>(
+    result_enum_ident: &Ident,
+    fallible: impl Iterator<Item = (&'a Ident, bool)>,
+) -> Option<ImplItem> {
+    let arms = fallible
+        .map(|(message_ident, is_blocking)| {
+            let check: Expr = if is_blocking {
+                parse_quote! { matches!(result, Ok(result) if FallibleResult::is_err(result)) }
+            } else {
+                parse_quote! { FallibleResult::is_err(result) }
+            };
+
+            quote! { #result_enum_ident::#message_ident(result) => #check, }
+        })
+        .collect::<Vec<_>>();
+
+    if arms.is_empty() {
+        return None;
+    }
+
+    Some(parse_quote! {
+        #[allow(unreachable_patterns)]
+        fn is_err(res: &Self::Result) -> bool {
+            match res {
+                #(#arms)*
+                _ => false,
+            }
+        }
+    })
+}
 
 // A struct contains Actor specific info.
 pub(crate) struct ActorInfo<'a> {
@@ -87,7 +377,7 @@ impl<'a> ActorInfo<'a> {
             });
             let result_type = handle
                 .message_return_type
-                .map(Clone::clone)
+                .cloned()
                 .unwrap_or(default_result_type);
 
             // construct message enum's new variant from message ident and type path
@@ -167,7 +457,7 @@ impl<'a> ActorInfo<'a> {
     }
 
     // Generate From<Message> for ActorMessage
-    pub(crate) fn from_trait(&mut self, handle_info: &[HandleMethodInfo]) -> &mut Self {
+    pub(crate) fn impl_from_trait(&mut self, handle_info: &[HandleMethodInfo]) -> &mut Self {
         let message_enum_ident = &self.message_enum_ident;
         let message_enum_type = &self.message_enum_type;
 
@@ -300,7 +590,7 @@ impl<'a> ActorInfo<'a> {
             });
             let result_type = handle
                 .message_return_type
-                .map(Clone::clone)
+                .cloned()
                 .unwrap_or(default_result_type);
 
             let mut path = Path {
@@ -556,7 +846,13 @@ impl<'a> ActorInfo<'a> {
                 trait_: Some((None, path, Default::default())),
                 self_ty: Box::new(Type::Path(message_type_path.clone())),
                 brace_token: Default::default(),
-                items: vec![ImplItem::Type(impl_type), ImplItem::Method(method)],
+                items: vec![ImplItem::Type(impl_type), ImplItem::Method(method)]
+                    .into_iter()
+                    .chain(priority_method(handle.is_high_priority))
+                    .chain(route_key_method(handle.has_route_key))
+                    .chain(retry_copy_method(handle.is_idempotent))
+                    .chain(map_is_err_method(handle.is_fallible))
+                    .collect(),
             });
 
             self.items.push(impl_item);
@@ -598,6 +894,13 @@ impl<'a> ActorInfo<'a> {
             ty: Box::new(message_enum_type),
         }));
 
+        let with_context = handle_info
+            .iter()
+            .any(|handle| handle.context_ident.is_some());
+        if with_context {
+            inputs.push(context_fn_arg());
+        }
+
         let mut path = Path {
             leading_colon: None,
             segments: Default::default(),
@@ -636,6 +939,13 @@ impl<'a> ActorInfo<'a> {
                     subpat: None,
                 }));
 
+                let mut method_block = handle.method_block.clone();
+                if let Some(ident) = handle.context_ident.as_ref() {
+                    method_block
+                        .stmts
+                        .insert(0, bind_context_stmt(ident, !handle.is_async));
+                }
+
                 // If the message have blocking attribute we wrap the method in runtime::spawn_blocking
                 let stmt1 = if handle.is_async {
                     Stmt::Local(Local {
@@ -654,7 +964,7 @@ impl<'a> ActorInfo<'a> {
                                 attrs: vec![],
                                 async_token: Default::default(),
                                 capture: Some(Default::default()),
-                                block: handle.method_block.clone(),
+                                block: method_block,
                             })),
                         )),
                         semi_token: Default::default(),
@@ -683,7 +993,7 @@ impl<'a> ActorInfo<'a> {
                         body: Box::new(Expr::Block(ExprBlock {
                             attrs: vec![],
                             label: None,
-                            block: handle.method_block.clone(),
+                            block: method_block,
                         })),
                     };
 
@@ -774,7 +1084,19 @@ impl<'a> ActorInfo<'a> {
             vec![attr_from_ident_str(vec!["handler"])]
         };
 
-        let handle = Item::Impl(ItemImpl {
+        // Handler::is_err would check the result of fallible messages.
+        let is_err = is_err_method(
+            result_enum_ident,
+            handle_info
+                .iter()
+                .filter(|handle| handle.is_fallible)
+                .map(|handle| {
+                    let message_ident = handle.message_type_path.path.get_ident().unwrap();
+                    (message_ident, !handle.is_async)
+                }),
+        );
+
+        let mut handle = ItemImpl {
             attrs,
             defaultness: None,
             unsafety: None,
@@ -797,7 +1119,7 @@ impl<'a> ActorInfo<'a> {
                     unsafety: None,
                     abi: None,
                     fn_token: Default::default(),
-                    ident: Ident::new("handle", Span::call_site()),
+                    ident: Ident::new(handle_method_name(with_context), Span::call_site()),
                     generics: Default::default(),
                     paren_token: Default::default(),
                     inputs,
@@ -824,9 +1146,36 @@ impl<'a> ActorInfo<'a> {
                     }))],
                 },
             })],
-        });
+        };
 
-        self.items.push(handle);
+        // Handler::always_run would check the messages marked as always_run.
+        let always_run = always_run_method(
+            &self.message_enum_ident,
+            handle_info
+                .iter()
+                .filter(|handle| handle.is_always_run)
+                .map(|handle| handle.message_type_path.path.get_ident().unwrap()),
+        );
+
+        // Handler::message_name would return the variant name of message.
+        let message_name = message_name_method(
+            &self.message_enum_ident,
+            handle_info
+                .iter()
+                .map(|handle| handle.message_type_path.path.get_ident().unwrap()),
+        );
+
+        if with_context {
+            handle.items.push(detached_handle_method(
+                &self.message_enum_ident,
+                result_enum_ident,
+            ));
+        }
+        handle.items.extend(is_err);
+        handle.items.extend(always_run);
+        handle.items.extend(message_name);
+
+        self.items.push(Item::Impl(handle));
 
         self
     }
@@ -839,7 +1188,13 @@ pub(crate) struct HandleMethodInfo<'a> {
     message_return_type: Option<&'a Type>,
     // method_signature: &'a Signature,
     method_block: &'a Block,
+    context_ident: Option<Ident>,
     is_async: bool,
+    is_fallible: bool,
+    is_always_run: bool,
+    is_high_priority: bool,
+    has_route_key: bool,
+    is_idempotent: bool,
 }
 
 impl<'a> HandleMethodInfo<'a> {
@@ -868,15 +1223,46 @@ impl<'a> HandleMethodInfo<'a> {
             _ => None,
         };
 
+        let context_ident = context_arg_ident(&method.sig.inputs);
+
         let is_async = method.sig.asyncness.is_some();
 
+        // #[fallible] attribute indicate the method returns a Result and it's error should be
+        // treated as a failure of actor.
+        let is_fallible = is_ident(&method.attrs, "fallible").is_some();
+
+        // #[always_run] attribute indicate the method should run even when the caller is gone.
+        let is_always_run = is_ident(&method.attrs, "always_run").is_some();
+
+        // #[priority = "high"] attribute push the message to the high priority lane of mailbox.
+        let is_high_priority = is_ident(&method.attrs, "priority")
+            .map(|attr| match attr.parse_meta() {
+                Ok(Meta::NameValue(MetaNameValue {
+                    lit: Lit::Str(lit), ..
+                })) => is_high(&lit.value()),
+                _ => panic!("#[priority = \"high\"] is malformed"),
+            })
+            .unwrap_or(false);
+
+        // #[route_key] attribute indicate the message implements RouteKey trait.
+        let has_route_key = is_ident(&method.attrs, "route_key").is_some();
+
+        // #[idempotent] attribute indicate the message implements Idempotent trait.
+        let is_idempotent = is_ident(&method.attrs, "idempotent").is_some();
+
         Self {
             message_var_ident,
             message_type_path,
             message_return_type,
             // method_signature: &method.sig,
             method_block: &method.block,
+            context_ident,
             is_async,
+            is_fallible,
+            is_always_run,
+            is_high_priority,
+            has_route_key,
+            is_idempotent,
         }
     }
 }
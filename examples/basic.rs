@@ -39,6 +39,7 @@ async fn main() {
 
     let res2 = address.send(msg2).await.unwrap();
 
+    #[allow(clippy::let_unit_value)]
     let res3 = address.send(msg3).await.unwrap();
 
     println!("We got result for Message1\r\nResult is: {}\r\n", res);
@@ -8,10 +8,15 @@ pub struct MyActor;
 pub struct Message1;
 pub struct Message2;
 pub struct Message3;
+pub struct Message4;
 
 // handler implement
 #[handler_v2]
 impl MyActor {
+    // #[always_run] attribute notify the macro the method should run even when the caller has
+    // dropped the future of Address::send or it's timed out.
+    // By default the message would be skipped.
+    #[always_run]
     async fn handle_msg1(&mut self, _msg1: Message1) {}
 
     // the name of handle method is not important at all.
@@ -26,6 +31,14 @@ impl MyActor {
         // method.
         16
     }
+
+    // #[fallible] attribute notify the macro the method returns a Result and the error would be
+    // treated as a failure of actor. The Result is returned to caller as it is.
+    // The actor would be restarted on error if Builder::restart_on_err is set.
+    #[fallible]
+    async fn handle_fallible(&mut self, _: Message4) -> Result<u32, std::io::Error> {
+        Err(std::io::Error::other("something went wrong"))
+    }
 }
 
 #[tokio::main]
@@ -53,5 +66,12 @@ async fn main() {
     let res3 = address.send(Message3).await;
     assert_eq!(16, res3.unwrap());
 
+    let res4 = address.send(Message4).await;
+    assert!(matches!(res4, Ok(Err(_))));
+
+    // send messages of the same type in one batch.
+    let res5 = address.send_batch(vec![Message2, Message2]).await.unwrap();
+    assert!(res5.into_iter().all(|res| matches!(res, Ok(8))));
+
     println!("example finished successfully");
 }
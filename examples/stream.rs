@@ -15,13 +15,6 @@ async fn main() {
     // by sending the stream to actor we can handle every stream item as a actor message
     // and return the result as a new stream
 
-    // impl From trait for auto converting stream item to a message type.
-    impl From<String> for Message1 {
-        fn from(from: String) -> Self {
-            Self { from }
-        }
-    }
-
     // send the stream to actor.type signature is for inferring what message type the stream would
     // be convert into and sent to actor.
     let mut new_stream = address.send_stream::<_, _, Message1>(stream);
@@ -55,6 +48,13 @@ pub struct Message1 {
     pub from: String,
 }
 
+// impl From trait for auto converting stream item to a message type.
+impl From<String> for Message1 {
+    fn from(from: String) -> Self {
+        Self { from }
+    }
+}
+
 #[handler_v2]
 impl Handler for MyActor {
     async fn handle(&mut self, msg: Message1) -> String {
@@ -1,22 +1,41 @@
 use core::future::Future;
 use core::pin::Pin;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use core::time::Duration;
 
-use crate::builder::{Builder, BuilderFnContainer, Config};
-use crate::context::ContextMessage;
+#[cfg(not(feature = "actix-runtime-mpsc"))]
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+#[cfg(not(feature = "actix-runtime-mpsc"))]
+use crate::autoscale::{ScaleEvent, MAX_SCALE_EVENTS};
+
+use crate::builder::{instance_channel, Builder, BuilderFnContainer, Config};
+use crate::circuit_breaker::Breaker;
+use crate::context::{ActorContextState, Context, ContextMessage};
+use crate::dead_letter::{DeadLetter, DeadLetterReason, DeadLetterSink};
+use crate::error::ActixSendError;
 use crate::interval::IntervalFutureSet;
+use crate::metrics::MetricsState;
+use crate::middleware::Middleware;
+use crate::object::AnyObjectContainer;
+use crate::rate_limit::RateLimiter;
 use crate::receiver::Receiver;
+use crate::retry::RetryPolicy;
+use crate::router::Router;
 use crate::sender::Sender;
+use crate::supervisor::Supervision;
 use crate::util::{
-    channel::bounded,
     future_handle::FutureHandler,
+    runtime,
     smart_pointer::{Lock, RefCounter},
 };
 
 #[cfg(not(feature = "actix-runtime-mpsc"))]
 macro_rules! actor {
-    ($($send:ident)*) => {
+    ($($send:ident)*; $($fut_send:ident)*) => {
         pub trait Actor
         where
             Self: Sized $( + $send)*,
@@ -25,10 +44,18 @@ macro_rules! actor {
             type Result: $($send)*;
 
             /// define a new builder for an new set of actor(s) with the async closure.
+            ///
+            /// *. The closure would be called again to rebuild the actor when it restarts. The
+            /// rebuild happens inside the actor's context so the future must be `Send` on
+            /// `tokio-runtime` and `async-std-runtime` where the context is spawned on a
+            /// multi-thread executor.
+            ///
+            /// *. `actix-runtime` and `actix-runtime-mpsc` run the context on a local executor and
+            /// do not require the future to be `Send`.
             fn builder<F, Fut>(f: F) -> Builder<Self>
             where
                 F: Fn() -> Fut + Send + Sync + 'static,
-                Fut: Future<Output = Self> + 'static,
+                Fut: Future<Output = Self> $( + $fut_send)* + 'static,
             {
                 Builder {
                     actor_builder: BuilderFnContainer::new(f),
@@ -57,8 +84,12 @@ macro_rules! actor {
     }
 }
 
-#[cfg(not(any(feature = "actix-runtime-mpsc")))]
-actor!(Send);
+#[cfg(not(any(feature = "actix-runtime", feature = "actix-runtime-mpsc")))]
+actor!(Send; Send);
+
+#[cfg(feature = "actix-runtime")]
+#[cfg(not(feature = "actix-runtime-mpsc"))]
+actor!(Send;);
 
 #[cfg(feature = "actix-runtime-mpsc")]
 pub trait Actor
@@ -118,8 +149,42 @@ where
     pub(crate) interval_futures: IntervalFutureSet<A>,
     // config for setting inherent from Builder.
     config: Config,
+    // builder function for rebuilding actor instance when restarting.
+    builder: BuilderFnContainer<A>,
+    // restart bookkeeping of the actors.
+    supervision: RefCounter<Supervision>,
+    // The count of delayed messages that are not pushed to mailbox yet.
+    delayed: RefCounter<AtomicUsize>,
+    // Notify when the count of delayed messages drops to zero.
+    flushed: RefCounter<Notify>,
+    // The count of messages skipped because their callers have gone away.
+    skipped: RefCounter<AtomicUsize>,
+    // The counter of dispatched messages for Router::RoundRobin.
+    next_route: RefCounter<AtomicUsize>,
+    // The messages handled and the time spent on them since last check of autoscaler.
+    handled: RefCounter<AtomicUsize>,
+    busy: RefCounter<AtomicU64>,
+    // The latest decisions of autoscaler.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    scale_events: RefCounter<Lock<VecDeque<ScaleEvent>>>,
+    // The latencies, timeouts of actor instances.
+    metrics: RefCounter<MetricsState>,
+    // The states of running actor instances.
+    instances: RefCounter<Lock<Vec<ActorContextState>>>,
+    // The id for the next actor instance spawned.
+    next_id: RefCounter<AtomicUsize>,
+    // The receivers of shared mailbox for spawning more actor instances at runtime.
+    // It's released when the last actor instance exits so the mailbox can be closed.
+    mailbox: RefCounter<Lock<Option<Mailbox<A>>>>,
+    // The token bucket of Builder::rate_limit.
+    limiter: Option<RefCounter<RateLimiter>>,
+    // The circuit breaker of Builder::circuit_breaker.
+    breaker: Option<RefCounter<Breaker>>,
 }
 
+// The receivers of normal and high priority lanes of a mailbox.
+pub(crate) type Mailbox<A> = (Receiver<ContextMessage<A>>, Receiver<ContextMessage<A>>);
+
 impl<A> Clone for ActorState<A>
 where
     A: Actor,
@@ -130,6 +195,22 @@ where
             handlers: self.handlers.clone(),
             interval_futures: self.interval_futures.clone(),
             config: self.config.clone(),
+            builder: self.builder.clone(),
+            supervision: self.supervision.clone(),
+            delayed: self.delayed.clone(),
+            flushed: self.flushed.clone(),
+            skipped: self.skipped.clone(),
+            next_route: self.next_route.clone(),
+            handled: self.handled.clone(),
+            busy: self.busy.clone(),
+            #[cfg(not(feature = "actix-runtime-mpsc"))]
+            scale_events: self.scale_events.clone(),
+            metrics: self.metrics.clone(),
+            instances: self.instances.clone(),
+            next_id: self.next_id.clone(),
+            mailbox: self.mailbox.clone(),
+            limiter: self.limiter.clone(),
+            breaker: self.breaker.clone(),
         }
     }
 }
@@ -138,30 +219,162 @@ impl<A> ActorState<A>
 where
     A: Actor + 'static,
 {
-    pub(crate) fn new(config: Config) -> Self {
+    pub(crate) fn new(config: Config, builder: BuilderFnContainer<A>) -> Self {
         Self {
             active: RefCounter::new(AtomicUsize::new(0)),
             handlers: RefCounter::new(Lock::new(Vec::new())),
             interval_futures: Default::default(),
+            supervision: RefCounter::new(Supervision::new(&config)),
+            delayed: RefCounter::new(AtomicUsize::new(0)),
+            flushed: RefCounter::new(Notify::new()),
+            skipped: RefCounter::new(AtomicUsize::new(0)),
+            next_route: RefCounter::new(AtomicUsize::new(0)),
+            handled: RefCounter::new(AtomicUsize::new(0)),
+            busy: RefCounter::new(AtomicU64::new(0)),
+            #[cfg(not(feature = "actix-runtime-mpsc"))]
+            scale_events: RefCounter::new(Lock::new(VecDeque::new())),
+            metrics: RefCounter::new(MetricsState::new(&config, core::any::type_name::<A>())),
+            instances: RefCounter::new(Lock::new(Vec::new())),
+            next_id: RefCounter::new(AtomicUsize::new(config.num)),
+            mailbox: RefCounter::new(Lock::new(None)),
+            limiter: config
+                .rate_limit
+                .clone()
+                .map(|limit| RefCounter::new(RateLimiter::new(limit))),
+            breaker: config
+                .circuit_breaker
+                .clone()
+                .map(|policy| RefCounter::new(Breaker::new(policy))),
             config,
+            builder,
         }
     }
 
-    pub(crate) fn broadcast_receiver(
-        &self,
-        broadcast_senders: &mut Vec<Sender<ContextMessage<A>>>,
-        num: usize,
-    ) -> Option<Receiver<ContextMessage<A>>> {
-        match self.allow_broadcast() {
-            true => {
-                let (tx_broadcast, rx_broadcast) = bounded::<ContextMessage<A>>(num);
+    pub(crate) async fn build_actor(&self) -> A {
+        self.builder.build().await
+    }
+
+    pub(crate) fn metrics(&self) -> &MetricsState {
+        &self.metrics
+    }
+
+    pub(crate) fn breaker(&self) -> Option<&RefCounter<Breaker>> {
+        self.breaker.as_ref()
+    }
 
-                broadcast_senders.push(Sender::from(tx_broadcast));
+    pub(crate) fn supervision(&self) -> &Supervision {
+        &self.supervision
+    }
+
+    pub(crate) fn inc_delayed(&self) {
+        self.delayed.fetch_add(1, Ordering::SeqCst);
+    }
 
-                Some(rx_broadcast.into())
+    pub(crate) fn dec_delayed(&self) {
+        if self.delayed.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.flushed.notify_waiters();
+        }
+    }
+
+    // cancel all delayed and interval tasks and wait for the delayed messages pushed to mailbox.
+    // Builder::timeout applies to the waiting.
+    pub(crate) async fn flush_delayed(&self) {
+        for handler in self.handlers.lock().iter() {
+            handler.cancel();
+        }
+
+        let flushed = async {
+            loop {
+                // a Notified future receives notify_waiters once it's created.
+                let notified = self.flushed.notified();
+                if self.delayed.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+                notified.await;
             }
-            false => None,
+        };
+
+        let _ = runtime::timeout(self.timeout(), flushed).await;
+    }
+
+    // count out an exiting actor instance and return true if it's the last one.
+    // The final state is reported when the actor instances are stopping by supervisor or
+    // Address::stop and the last one would shutdown the state.
+    pub(crate) fn exit(&self, state: ActorContextState, report: bool) -> bool {
+        let count_out = || {
+            self.active.fetch_sub(UNIT, Ordering::SeqCst);
+            self.current_active() == 0
+        };
+
+        if !report {
+            return count_out();
+        }
+
+        let last = self.supervision.report(state, count_out);
+        if last {
+            self.shutdown();
         }
+        last
+    }
+
+    // construct a channel for every actor instance. It's used for routing, broadcast and targeted
+    // messages and follows the capacity and policy of mailbox.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn instance_channels(
+        &self,
+        num: usize,
+    ) -> (Vec<Sender<ContextMessage<A>>>, Vec<Mailbox<A>>) {
+        (0..num).map(|_| instance_channel::<A>(&self.config)).unzip()
+    }
+
+    // register or update the state of a running actor instance.
+    pub(crate) fn register_instance(&self, state: ActorContextState) {
+        let mut instances = self.instances.lock();
+        match instances.iter_mut().find(|s| s.id() == state.id()) {
+            Some(s) => *s = state,
+            None => instances.push(state),
+        }
+    }
+
+    pub(crate) fn remove_instance(&self, id: usize) {
+        self.instances.lock().retain(|s| s.id() != id);
+        self.metrics.remove_instance(id);
+    }
+
+    // reserve an id for a new actor instance.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn next_id(&self) -> usize {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    // check if the id has been given to an actor instance.
+    pub(crate) fn is_known_id(&self, id: usize) -> bool {
+        id < self.next_id.load(Ordering::SeqCst)
+    }
+
+    // mpsc receiver can not be cloned so there is no way to spawn more actor instances.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn set_mailbox(
+        &self,
+        rx: &Receiver<ContextMessage<A>>,
+        rx_high: &Receiver<ContextMessage<A>>,
+    ) {
+        *self.mailbox.lock() = Some((rx.clone(), rx_high.clone()));
+    }
+
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn mailbox(&self) -> Option<Mailbox<A>> {
+        self.mailbox.lock().clone()
+    }
+
+    pub(crate) fn release_mailbox(&self) {
+        self.mailbox.lock().take();
+    }
+
+    pub(crate) fn instances(&self) -> Vec<ActorContextState> {
+        let mut instances = self.instances.lock().clone();
+        instances.sort_by_key(|s| s.id());
+        instances
     }
 
     pub(crate) fn push_handler(&self, handler: Vec<FutureHandler<A>>) {
@@ -179,6 +392,19 @@ where
         self.config.handle_delayed_on_shutdown
     }
 
+    pub(crate) fn router(&self) -> Router {
+        self.config.router
+    }
+
+    // the counter for Router::RoundRobin.
+    pub(crate) fn next_route(&self) -> &AtomicUsize {
+        &self.next_route
+    }
+
+    pub(crate) fn middlewares(&self) -> &[Arc<dyn Middleware>] {
+        &self.config.middlewares
+    }
+
     pub(crate) fn allow_broadcast(&self) -> bool {
         self.config.allow_broadcast
     }
@@ -187,47 +413,127 @@ where
         self.config.allow_subscribe
     }
 
-    pub(crate) fn inc_active(&self) -> bool {
-        self.modify_active(|active| active + UNIT)
+    pub(crate) fn inc_active(&self) {
+        self.active.fetch_add(UNIT, Ordering::SeqCst);
     }
 
-    pub(crate) fn dec_active(&self) -> bool {
-        self.modify_active(|active| active - UNIT)
+    pub(crate) fn current_active(&self) -> usize {
+        let state = self.active.load(Ordering::SeqCst);
+        state >> 1
     }
 
-    fn modify_active<F>(&self, mut f: F) -> bool
-    where
-        F: FnMut(usize) -> usize,
-    {
-        let mut active = self.active.load(Ordering::Relaxed);
-        loop {
-            if active & MARKER != 0 {
-                return false;
-            }
+    pub(crate) fn inc_skipped(&self) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+        self.metrics.canceled();
+    }
 
-            let new = f(active);
+    pub(crate) fn skipped_count(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
 
-            match self.active.compare_exchange_weak(
-                active,
-                new,
-                Ordering::SeqCst,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => return true,
-                Err(a) => active = a,
-            }
+    // the latency of handling messages is only measured when metrics, middlewares or autoscaler
+    // would use it.
+    pub(crate) fn is_timed(&self) -> bool {
+        #[cfg(not(feature = "actix-runtime-mpsc"))]
+        if self.config.autoscale.is_some() {
+            return true;
         }
+        self.metrics.is_enabled() || !self.config.middlewares.is_empty()
     }
 
-    pub(crate) fn current_active(&self) -> usize {
-        let state = self.active.load(Ordering::SeqCst);
-        state >> 1
+    pub(crate) fn record_handled(&self, dur: Duration) {
+        self.handled.fetch_add(1, Ordering::Relaxed);
+        self.busy
+            .fetch_add(dur.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    // take the messages handled and the time spent on them since last take.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn take_load(&self) -> (usize, Duration) {
+        let handled = self.handled.swap(0, Ordering::Relaxed);
+        let busy = self.busy.swap(0, Ordering::Relaxed);
+        (handled, Duration::from_nanos(busy))
+    }
+
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn push_scale_event(&self, event: ScaleEvent) {
+        let mut events = self.scale_events.lock();
+        if events.len() == MAX_SCALE_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn scale_events(&self) -> Vec<ScaleEvent> {
+        self.scale_events.lock().iter().cloned().collect()
+    }
+
+    pub(crate) fn dead_letters(&self) -> Option<&Arc<dyn DeadLetterSink>> {
+        self.config.dead_letters.as_ref()
+    }
+
+    // send a message could not be delivered to the dead letter sink.
+    pub(crate) fn dead_letter(&self, msg: ContextMessage<A>, reason: DeadLetterReason) {
+        if self.dead_letters().is_none() {
+            return;
+        }
+        if let Some(msg) = msg.into_message() {
+            self.dead_letter_message(msg, reason);
+        }
+    }
+
+    pub(crate) fn dead_letter_message(&self, msg: A::Message, reason: DeadLetterReason) {
+        if let Some(sink) = self.dead_letters() {
+            let letter = DeadLetter::new(
+                core::any::type_name::<A>(),
+                reason,
+                AnyObjectContainer::pack(msg),
+            );
+            sink.dead_letter(letter);
+        }
+    }
+
+    pub(crate) fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.config.retry.as_ref()
     }
 
     pub(crate) fn timeout(&self) -> Duration {
         self.config.timeout
     }
 
+    // wait for a token of rate limiter. Builder::timeout applies to the waiting.
+    pub(crate) async fn throttle(&self) -> Result<(), ActixSendError> {
+        let wait = self.reserve_token(self.timeout())?;
+        if wait > Duration::from_secs(0) {
+            runtime::delay_for(wait).await;
+        }
+        Ok(())
+    }
+
+    // take a token of rate limiter without waiting.
+    pub(crate) fn try_throttle(&self) -> Result<(), ActixSendError> {
+        self.reserve_token(Duration::from_secs(0)).map(|_| ())
+    }
+
+    // take a token of rate limiter and block the current thread for it.
+    pub(crate) fn throttle_blocking(&self) -> Result<(), ActixSendError> {
+        let wait = self.reserve_token(self.timeout())?;
+        if wait > Duration::from_secs(0) {
+            std::thread::sleep(wait);
+        }
+        Ok(())
+    }
+
+    fn reserve_token(&self, max: Duration) -> Result<Duration, ActixSendError> {
+        match self.limiter.as_ref() {
+            Some(limiter) => limiter
+                .reserve(self.current_active(), max)
+                .ok_or(ActixSendError::RateLimited),
+            None => Ok(Duration::from_secs(0)),
+        }
+    }
+
     pub(crate) fn shutdown(&self) {
         // We write marker to the last bit of active usize.
         self.active.fetch_or(MARKER, Ordering::Relaxed);
@@ -249,6 +555,43 @@ where
     Self: Actor,
 {
     async fn handle(&mut self, msg: Self::Message) -> Self::Result;
+
+    /// Handle a message with the context of actor instance. Default to `Handler::handle`.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this when any handle method takes
+    /// `&mut Context<Self>` as argument.
+    async fn handle_with_context(
+        &mut self,
+        msg: Self::Message,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        self.handle(msg).await
+    }
+
+    /// Check if the result of `Handler::handle` is an error.
+    ///
+    /// The actor instance would be rebuilt with the builder function when this returns true and
+    /// `Builder::restart_on_err` is set.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this for messages marked as fallible.
+    fn is_err(_res: &Self::Result) -> bool {
+        false
+    }
+
+    /// Check if the message should be handled even when the caller of `Address::send` has gone
+    /// away. Messages of gone callers are skipped by default.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this for messages marked as always_run.
+    fn always_run(_msg: &Self::Message) -> bool {
+        false
+    }
+
+    /// The name of message used by metrics.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this with the variant name of message.
+    fn message_name(_msg: &Self::Message) -> &'static str {
+        core::any::type_name::<Self::Message>()
+    }
 }
 
 #[cfg(feature = "actix-runtime-mpsc")]
@@ -258,4 +601,53 @@ where
     Self: Actor,
 {
     async fn handle(&mut self, msg: Self::Message) -> Self::Result;
+
+    /// Handle a message with the context of actor instance. Default to `Handler::handle`.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this when any handle method takes
+    /// `&mut Context<Self>` as argument.
+    async fn handle_with_context(
+        &mut self,
+        msg: Self::Message,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        self.handle(msg).await
+    }
+
+    /// Check if the result of `Handler::handle` is an error.
+    ///
+    /// The actor instance would be rebuilt with the builder function when this returns true and
+    /// `Builder::restart_on_err` is set.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this for messages marked as fallible.
+    fn is_err(_res: &Self::Result) -> bool {
+        false
+    }
+
+    /// Check if the message should be handled even when the caller of `Address::send` has gone
+    /// away. Messages of gone callers are skipped by default.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this for messages marked as always_run.
+    fn always_run(_msg: &Self::Message) -> bool {
+        false
+    }
+
+    /// The name of message used by metrics.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this with the variant name of message.
+    fn message_name(_msg: &Self::Message) -> &'static str {
+        core::any::type_name::<Self::Message>()
+    }
+}
+
+// a helper trait for the result of fallible handle methods.
+// It's used by #[actor_mod] and #[handler_v2] macro to check if the handle method returned an error.
+pub trait FallibleResult {
+    fn is_err(&self) -> bool;
+}
+
+impl<T, E> FallibleResult for Result<T, E> {
+    fn is_err(&self) -> bool {
+        Result::is_err(self)
+    }
 }
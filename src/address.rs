@@ -1,24 +1,151 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
 use core::future::Future;
 use core::marker::PhantomData;
 use core::pin::Pin;
 use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll};
 use core::time::Duration;
 
+use std::time::Instant;
+
 use futures_util::stream::{FuturesUnordered, Stream, StreamExt};
 
 use crate::actor::{Actor, ActorState};
+#[cfg(not(feature = "actix-runtime-mpsc"))]
+use crate::autoscale::ScaleEvent;
+use crate::circuit_breaker::{self, Breaker, CircuitState, Permit};
 use crate::context::{
     ActorContextState, ContextMessage, DelayedMessage, InstantMessage, IntervalMessage,
 };
+use crate::dead_letter::DeadLetterReason;
+use crate::envelope::Envelope;
 use crate::error::ActixSendError;
+use crate::metrics::Metrics;
 use crate::object::AnyObjectContainer;
+use crate::retry::{Idempotent, RetryPolicy};
 use crate::sender::{GroupSender, Sender, WeakGroupSender, WeakSender};
 use crate::stream::{ActorSkipStream, ActorStream};
 use crate::subscribe::Subscribe;
 use crate::util::{
-    channel::oneshot_channel, future_handle::FutureHandler, runtime, smart_pointer::RefCounter,
+    channel::{oneshot_channel, unbounded, OneShotReceiver, OneShotSender, TrySendError},
+    future_handle::FutureHandler,
+    runtime,
+    smart_pointer::RefCounter,
 };
 
+/// The way of handling messages left in mailbox when stopping actor(s).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopMode {
+    /// Handle all messages in mailbox before stop.
+    Drain,
+    /// Drop all messages in mailbox. Their senders would get `ActixSendError::Closed`.
+    Immediate,
+    /// Handle messages in mailbox until the deadline and drop the rest like `StopMode::Immediate`.
+    Deadline(Duration),
+}
+
+/// The lane of mailbox a message is pushed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Pushed to a lane that actor(s) would receive from before the normal one. The lane follows
+    /// `Builder::mailbox_capacity` and `Builder::mailbox_policy` on it's own.
+    ///
+    /// *. The lane carries control messages so `MailboxPolicy::DropOldest` falls back to
+    /// `MailboxPolicy::DropNewest` on it.
+    High,
+    /// Pushed to the mailbox following `Builder::mailbox_capacity` and `Builder::mailbox_policy`.
+    Normal,
+}
+
+/// The delivery of a message sent by `Address::do_send_tracked` or `Address::do_run_tracked`.
+///
+/// *. Await it to know the message is pushed to mailbox. It does not wait for the message to be
+/// handled so `ActixSendError::Closed`, `ActixSendError::Full` and errors of
+/// `Builder::rate_limit` are the only failures.
+///
+/// *. It can be dropped at any time. The message is still delivered in background and goes to the
+/// sink of `Builder::dead_letters` if it's not.
+pub struct SendHandle {
+    rx: OneShotReceiver<Result<(), ActixSendError>>,
+}
+
+impl Future for SendHandle {
+    type Output = Result<(), ActixSendError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // the delivery task is dropped when the runtime is shutting down.
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|res| res.unwrap_or(Err(ActixSendError::Canceled)))
+    }
+}
+
+impl Debug for SendHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("SendHandle").finish()
+    }
+}
+
+/// The result of a message pushed to mailbox by `Address::try_send`.
+///
+/// *. Dropping it before the message is handled cancels the message like dropping the future of
+/// `Address::send`.
+pub struct ReplyFuture<A, M>
+where
+    A: Actor,
+{
+    rx: OneShotReceiver<Result<A::Result, ActixSendError>>,
+    // the permit of Builder::circuit_breaker recorded with the result.
+    permit: Option<Permit>,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<A, M> ReplyFuture<A, M>
+where
+    A: Actor,
+{
+    fn new(rx: OneShotReceiver<Result<A::Result, ActixSendError>>, permit: Option<Permit>) -> Self {
+        Self {
+            rx,
+            permit,
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<A, M> Future for ReplyFuture<A, M>
+where
+    A: Actor,
+    M: MapResult<A::Result>,
+{
+    type Output = Result<<M as MapResult<A::Result>>::Output, ActixSendError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let res = match Pin::new(&mut self.rx).poll(cx) {
+            Poll::Ready(res) => res
+                .map_err(|_| ActixSendError::Canceled)
+                .and_then(|res| res)
+                .and_then(M::map),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if let Some(permit) = self.permit.take() {
+            permit.record(&res, M::is_err);
+        }
+
+        Poll::Ready(res)
+    }
+}
+
+impl<A, M> Debug for ReplyFuture<A, M>
+where
+    A: Actor,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ReplyFuture").finish()
+    }
+}
+
 // A channel sender for communicating with actor(s).
 pub struct Address<A>
 where
@@ -26,7 +153,8 @@ where
 {
     strong_count: RefCounter<AtomicUsize>,
     tx: Sender<ContextMessage<A>>,
-    tx_subs: Option<GroupSender<A>>,
+    // senders of every actor instance's own mailbox.
+    tx_subs: GroupSender<A>,
     subs: Option<Subscribe>,
     state: ActorState<A>,
 }
@@ -52,7 +180,7 @@ where
     A: Actor,
 {
     fn drop(&mut self) {
-        if self.strong_count.fetch_sub(1, Ordering::Acquire) == 0 {
+        if self.strong_count.fetch_sub(1, Ordering::Acquire) == 1 {
             self.state.shutdown();
         }
     }
@@ -67,7 +195,7 @@ where
         WeakAddress {
             strong_count: self.strong_count.clone(),
             tx: self.tx.downgrade(),
-            tx_subs: self.tx_subs.as_ref().map(|sub| sub.downgrade()),
+            tx_subs: self.tx_subs.downgrade(),
             state: self.state.clone(),
         }
     }
@@ -77,6 +205,14 @@ where
         self.state.current_active()
     }
 
+    /// The number of messages skipped by actor(s) because their callers have gone away.
+    ///
+    /// *. A caller is gone when the future of `Address::send`(and alike) is dropped or timed out
+    /// before actor start handling the message.
+    pub fn skipped_count(&self) -> usize {
+        self.state.skipped_count()
+    }
+
     pub(crate) fn new(
         tx: Sender<ContextMessage<A>>,
         tx_subs: GroupSender<A>,
@@ -88,12 +224,6 @@ where
             None
         };
 
-        let tx_subs = if state.allow_broadcast() {
-            Some(tx_subs)
-        } else {
-            None
-        };
-
         Self {
             strong_count: RefCounter::new(AtomicUsize::new(1)),
             tx,
@@ -107,11 +237,154 @@ where
         self.tx.downgrade()
     }
 
-    fn send_timeout(
+    pub(crate) fn state(&self) -> &ActorState<A> {
+        &self.state
+    }
+
+    // push message to the mailbox picked by router with the timeout from Builder::timeout.
+    async fn push_routed(
+        &self,
+        msg: ContextMessage<A>,
+        key: Option<u64>,
+    ) -> Result<(), ActixSendError> {
+        let timeout = self.state.timeout();
+        match self.route(key) {
+            Some(tx) => tx.send_timeout(msg, timeout).await,
+            None => self.tx.send_timeout(msg, timeout).await,
+        }
+        .map_err(|e| self.observe(e))
+    }
+
+    // count the timeouts returned to callers.
+    fn observe(&self, e: ActixSendError) -> ActixSendError {
+        if let ActixSendError::Timeout = e {
+            self.state.metrics().timed_out();
+        }
+        e
+    }
+
+    // push a message without waiting in a spawned task and report the delivery to tx.
+    // The message goes to the dead letter sink if it's not delivered.
+    fn spawn_deliver(
+        &self,
+        msg: ContextMessage<A>,
+        tx: Option<OneShotSender<Result<(), ActixSendError>>>,
+    ) {
+        let this = self.route(None).unwrap_or_else(|| self.tx.clone());
+        let state = self.state.clone();
+        runtime::spawn(async move {
+            let res = match state.throttle().await {
+                Ok(()) => match this.deliver(msg).await {
+                    Ok(()) => Ok(()),
+                    Err(TrySendError::Closed(msg)) => {
+                        state.dead_letter(msg, DeadLetterReason::Closed);
+                        Err(ActixSendError::Closed)
+                    }
+                    Err(TrySendError::Full(msg)) => {
+                        state.dead_letter(msg, DeadLetterReason::Full);
+                        Err(ActixSendError::Full)
+                    }
+                },
+                Err(e) => Err(e),
+            };
+            // the handle could be dropped already.
+            if let Some(tx) = tx {
+                let _ = tx.send(res);
+            }
+        });
+    }
+
+    // take a permit of Builder::circuit_breaker for a call finishing outside of Address::guard.
+    fn permit(&self) -> Result<Option<Permit>, ActixSendError> {
+        self.state.breaker().map(Breaker::acquire).transpose()
+    }
+
+    // call through the circuit breaker of Builder::circuit_breaker.
+    // is_err checks if the output is an error returned by handler.
+    async fn guard<F, R>(&self, fut: F, is_err: fn(&R) -> bool) -> Result<R, ActixSendError>
+    where
+        F: Future<Output = Result<R, ActixSendError>>,
+    {
+        let breaker = match self.state.breaker() {
+            Some(breaker) => breaker,
+            None => return fut.await,
+        };
+
+        let permit = breaker.acquire()?;
+        let res = fut.await;
+        permit.record(&res, is_err);
+        res
+    }
+
+    // boxed futures have no handler error. Only panics, timeouts and closed mailbox count toward
+    // the circuit breaker.
+    fn no_handler_err<R>(_: &R) -> bool {
+        false
+    }
+
+    // pick the mailbox of actor instance with the router.
+    // None when messages are not routed or all actor instances are closed.
+    fn route(&self, key: Option<u64>) -> Option<Sender<ContextMessage<A>>> {
+        self.tx_subs
+            .route(self.state.router(), key, self.state.next_route())
+    }
+
+    // the mailbox of actor instance with the given id.
+    fn instance(&self, id: usize) -> Result<Sender<ContextMessage<A>>, ActixSendError> {
+        match self.tx_subs.get(id) {
+            Some(tx) if !tx.is_closed() => Ok(tx),
+            _ if self.state.is_known_id(id) => Err(ActixSendError::InstanceClosed),
+            _ => Err(ActixSendError::UnknownInstance),
+        }
+    }
+
+    // push message to the mailbox of actor instance with the given id.
+    async fn push_to(&self, id: usize, msg: ContextMessage<A>) -> Result<(), ActixSendError> {
+        self.instance(id)?
+            .send_timeout(msg, self.state.timeout())
+            .await
+            .map_err(|e| match e {
+                ActixSendError::Closed => ActixSendError::InstanceClosed,
+                e => self.observe(e),
+            })
+    }
+
+    // push control message to the mailbox of actor instance with the given id.
+    // It waits for the room of mailbox and is never dropped by Builder::mailbox_policy.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    async fn push_control_to(
         &self,
+        id: usize,
         msg: ContextMessage<A>,
-    ) -> impl Future<Output = Result<(), ActixSendError>> + '_ {
-        self.tx.send_timeout(msg, self.state.timeout())
+    ) -> Result<(), ActixSendError> {
+        self.instance(id)?
+            .high_lane()
+            .send_control(msg, self.state.timeout())
+            .await
+            .map_err(|e| match e {
+                ActixSendError::Closed => ActixSendError::InstanceClosed,
+                e => self.observe(e),
+            })
+    }
+
+    // push message to the high priority lane of mailbox with the timeout from Builder::timeout.
+    async fn push_high(&self, msg: ContextMessage<A>) -> Result<(), ActixSendError> {
+        self.tx
+            .high_lane()
+            .send_timeout(msg, self.state.timeout())
+            .await
+            .map_err(|e| self.observe(e))
+    }
+
+    // push control message to the high priority lane of mailbox with the timeout from
+    // Builder::timeout. It waits for the room of mailbox and is never dropped by
+    // Builder::mailbox_policy.
+    async fn push_control(&self, msg: ContextMessage<A>) -> Result<(), ActixSendError> {
+        self.tx
+            .high_lane()
+            .send_control(msg, self.state.timeout())
+            .await
+            .map_err(|e| self.observe(e))
     }
 }
 
@@ -120,32 +393,455 @@ where
     A: Actor,
 {
     /// Send a message to actor(s) and await for result.
+    ///
+    /// *. `Builder::timeout` only applies to pushing message to mailbox. Use
+    /// `Address::send_timeout` to bound the whole round trip.
+    ///
+    /// *. Message marked with `#[message(priority = "high")]` is sent with `Priority::High`.
     #[must_use = "futures do nothing unless you `.await` or poll them"]
     pub async fn send<M>(
         &self,
         msg: M,
     ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        self.send_with_priority(msg, M::priority()).await
+    }
+
+    /// Send a message to actor(s) with the given priority and await for result.
+    ///
+    /// *. `Priority::High` messages are handled before the normal ones in mailbox.
+    /// The high priority lane has it's own capacity and `Builder::mailbox_policy` and
+    /// `Builder::timeout` apply to it like the normal one.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub async fn send_with_priority<M>(
+        &self,
+        msg: M,
+        priority: Priority,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        match self.state.retry_policy() {
+            Some(policy) => {
+                self.send_retrying(msg, priority, policy, M::retry_copy)
+                    .await
+            }
+            None => {
+                self.guard(self.send_throttled(None, msg, priority), M::is_err)
+                    .await
+            }
+        }
+    }
+
+    /// Send an idempotent message to actor(s) and retry on failure following the given policy.
+    ///
+    /// *. Every attempt goes through `Builder::rate_limit` and `Builder::circuit_breaker`.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub async fn send_retry<M>(
+        &self,
+        msg: M,
+        policy: &RetryPolicy,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result> + Idempotent,
+    {
+        self.send_retrying(msg, M::priority(), policy, |msg| Some(msg.clone()))
+            .await
+    }
+
+    // send a message and retry while it can be copied for the next attempt.
+    async fn send_retrying<M, F>(
+        &self,
+        mut msg: M,
+        priority: Priority,
+        policy: &RetryPolicy,
+        copy: F,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+        F: Fn(&M) -> Option<M>,
+    {
+        let mut attempt = 1;
+        loop {
+            let next = match attempt < policy.max_attempts() {
+                true => copy(&msg),
+                false => None,
+            };
+
+            let res = self
+                .guard(self.send_throttled(None, msg, priority), M::is_err)
+                .await;
+
+            let retry = match &res {
+                Ok(output) => M::is_err(output) && policy.should_retry_handler_err(),
+                Err(e) => policy.should_retry(e),
+            };
+
+            match next {
+                Some(next) if retry => {
+                    runtime::delay_for(policy.wait(attempt)).await;
+                    msg = next;
+                    attempt += 1;
+                }
+                _ => return res,
+            }
+        }
+    }
+
+    /// Send messages to actor(s) as one batch and await for their results in the same order.
+    ///
+    /// The batch is pushed to mailbox once and handled by one actor instance in sequence so the
+    /// cost of channel and result delivery is paid once for all messages.
+    ///
+    /// *. The outer error is returned when the batch can not be pushed to mailbox. Every message
+    /// has it's own result when the batch is handled.
+    ///
+    /// *. Every message takes a token of `Builder::rate_limit`. The batch is routed with the key of
+    /// it's first message and sent with the priority of message type.
+    ///
+    /// *. When the actor panics the rest of batch is not handled and would return
+    /// `ActixSendError::Closed`.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    #[allow(clippy::type_complexity)]
+    pub async fn send_batch<I, M>(
+        &self,
+        msgs: I,
+    ) -> Result<Vec<Result<<M as MapResult<A::Result>>::Output, ActixSendError>>, ActixSendError>
+    where
+        I: IntoIterator<Item = M>,
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        let msgs = msgs.into_iter().collect::<Vec<_>>();
+        if msgs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.guard(
+            async {
+                for _ in 0..msgs.len() {
+                    self.state.throttle().await?;
+                }
+
+                let (tx, rx) = oneshot_channel();
+
+                let key = msgs[0].route_key();
+                let msgs = msgs.into_iter().map(Into::into).collect();
+                let msg = ContextMessage::instant(InstantMessage::Batch(tx, msgs));
+
+                match M::priority() {
+                    Priority::High => self.push_high(msg).await?,
+                    Priority::Normal => self.push_routed(msg, key).await?,
+                }
+
+                let res = rx.await.map_err(|_| ActixSendError::Canceled)??;
+
+                Ok(res.into_iter().map(|res| res.and_then(M::map)).collect())
+            },
+            // the batch fails when any of the messages failed.
+            |res: &Vec<_>| {
+                res.iter().any(|res| match res {
+                    Ok(output) => M::is_err(output),
+                    Err(e) => circuit_breaker::is_failure(e),
+                })
+            },
+        )
+        .await
+    }
+
+    /// Push a message to mailbox without waiting and return a future for the result.
+    ///
+    /// It can be called from non-async code like `Drop` impls and callbacks.
+    ///
+    /// *. `ActixSendError::Full` or `ActixSendError::Closed` would return immediately when the
+    /// message can not be pushed. `ActixSendError::RateLimited` would return when
+    /// `Builder::rate_limit` has no token left.
+    ///
+    /// *. Use `Address::do_send` when the result is not needed.
+    pub fn try_send<M>(&self, msg: M) -> Result<ReplyFuture<A, M>, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        self.state.try_throttle()?;
+        let permit = self.permit()?;
+
+        let priority = M::priority();
+        let (msg, key, rx) = self.with_reply(None, msg);
+
+        let res = match priority {
+            Priority::High => self.tx.high_lane().try_send_with_policy(msg),
+            Priority::Normal => match self.route(key) {
+                Some(tx) => tx.try_send_with_policy(msg),
+                None => self.tx.try_send_with_policy(msg),
+            },
+        }
+        .map_err(Into::into);
+
+        if let Err(e) = res {
+            if let Some(permit) = permit {
+                permit.record_err(&e);
+            }
+            return Err(e);
+        }
+
+        // the result is recorded with M::is_err when the future resolves.
+        Ok(ReplyFuture::new(rx, permit))
+    }
+
+    /// Send a message to actor(s) and block the current thread until the result is returned.
+    ///
+    /// It's for sending from threads outside of async runtime like the ones of `std::thread` and
+    /// the blocking threads of runtime.
+    ///
+    /// *. `ActixSendError::Blocking` would return when it's called in async context as it would
+    /// block the thread actor(s) could be running on.
+    ///
+    /// *. `Builder::timeout` bounds the wait for `Builder::rate_limit`, pushing message to mailbox
+    /// and receiving the result separately. `ActixSendError::Timeout` would return when any of
+    /// them is reached.
+    pub fn blocking_send<M>(
+        &self,
+        msg: M,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        if runtime::in_runtime() {
+            return Err(ActixSendError::Blocking);
+        }
+
+        self.state.throttle_blocking()?;
+        let permit = self.permit()?;
+
+        let timeout = self.state.timeout();
+        let priority = M::priority();
+        let (msg, key, rx) = self.with_reply(None, msg);
+
+        let res = runtime::block_on(timeout, async {
+            match priority {
+                Priority::High => self.tx.high_lane().send_with_policy(msg).await,
+                Priority::Normal => match self.route(key) {
+                    Some(tx) => tx.send_with_policy(msg).await,
+                    None => self.tx.send_with_policy(msg).await,
+                },
+            }
+        })
+        .and_then(|res| res)
+        .map_err(|e| self.observe(e));
+
+        if let Err(e) = res {
+            if let Some(permit) = permit {
+                permit.record_err(&e);
+            }
+            return Err(e);
+        }
+
+        // the message is skipped by actor when the caller timed out.
+        let res = runtime::block_on(timeout, ReplyFuture::<A, M>::new(rx, None))
+            .and_then(|res| res)
+            .map_err(|e| self.observe(e));
+        if let Some(permit) = permit {
+            permit.record(&res, M::is_err);
+        }
+        res
+    }
+
+    /// Send a message wrapped in the given envelope to actor(s) and await for result.
+    ///
+    /// The envelope can be read with `Context::envelope` when handling the message.
+    ///
+    /// *. When the envelope has a deadline it bounds the whole round trip like
+    /// `Address::send_timeout`.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub async fn send_with<M>(
+        &self,
+        envelope: Envelope,
+        msg: M,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        let deadline = envelope.deadline();
+        let fut = self.send_throttled(Some(envelope), msg, M::priority());
+
+        self.guard(
+            async {
+                match deadline {
+                    Some(deadline) => {
+                        let dur = deadline.saturating_duration_since(Instant::now());
+                        runtime::timeout(dur, fut)
+                            .await
+                            .map_err(|e| self.observe(e))?
+                    }
+                    None => fut.await,
+                }
+            },
+            M::is_err,
+        )
+        .await
+    }
+
+    // send a message after waiting for the rate limiter.
+    async fn send_throttled<M>(
+        &self,
+        envelope: Option<Envelope>,
+        msg: M,
+        priority: Priority,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        self.state.throttle().await?;
+        self.send_envelope(envelope, msg, priority).await
+    }
+
+    async fn send_envelope<M>(
+        &self,
+        envelope: Option<Envelope>,
+        msg: M,
+        priority: Priority,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        let (msg, key, rx) = self.with_reply(envelope, msg);
+
+        match priority {
+            Priority::High => self.push_high(msg).await?,
+            Priority::Normal => self.push_routed(msg, key).await?,
+        }
+
+        ReplyFuture::<A, M>::new(rx, None).await
+    }
+
+    // wrap a message with the channel for it's result and the hashed key for router.
+    #[allow(clippy::type_complexity)]
+    fn with_reply<M>(
+        &self,
+        envelope: Option<Envelope>,
+        msg: M,
+    ) -> (
+        ContextMessage<A>,
+        Option<u64>,
+        OneShotReceiver<Result<A::Result, ActixSendError>>,
+    )
     where
         M: Into<A::Message> + MapResult<A::Result>,
     {
         let (tx, rx) = oneshot_channel();
 
-        let msg = ContextMessage::Instant(InstantMessage::Static(Some(tx), msg.into()));
+        let key = msg.route_key();
+        let msg = InstantMessage::Static(Some(tx), msg.into());
+        let msg = match envelope {
+            Some(envelope) => ContextMessage::instant_with(msg, envelope),
+            None => ContextMessage::instant(msg),
+        };
+
+        (msg, key, rx)
+    }
+
+    /// Send a message to actor(s) and await for result with a deadline.
+    ///
+    /// *. The deadline covers the whole round trip: pushing message to mailbox, handling it and
+    /// receiving the result. `ActixSendError::Timeout` would return when it's reached.
+    ///
+    /// *. A message already in mailbox is skipped by actor after the timeout unless it's marked as
+    /// always_run. See `Handler::always_run`.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub async fn send_timeout<M>(
+        &self,
+        msg: M,
+        dur: Duration,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        let fut = self.send_throttled(None, msg, M::priority());
+
+        self.guard(
+            async {
+                runtime::timeout(dur, fut)
+                    .await
+                    .map_err(|e| self.observe(e))?
+            },
+            M::is_err,
+        )
+        .await
+    }
+
+    /// Send a message to the actor instance with the given id and await for result.
+    ///
+    /// *. `ActixSendError::UnknownInstance` would return if there is no actor instance with the id.
+    /// `ActixSendError::InstanceClosed` would return if the actor instance is stopped.
+    ///
+    /// *. The ids and generations of running actor instances can be found with `Address::instances`
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub async fn send_to<M>(
+        &self,
+        id: usize,
+        msg: M,
+    ) -> Result<<M as MapResult<A::Result>>::Output, ActixSendError>
+    where
+        M: Into<A::Message> + MapResult<A::Result>,
+    {
+        self.guard(
+            async {
+                self.state.throttle().await?;
+
+                let (tx, rx) = oneshot_channel();
+
+                let msg = ContextMessage::instant(InstantMessage::Static(Some(tx), msg.into()));
+
+                self.push_to(id, msg).await?;
 
-        self.send_timeout(msg).await?;
+                let res = rx.await.map_err(|_| ActixSendError::Canceled)??;
 
-        let res = rx.await.map_err(|_| ActixSendError::Canceled)?;
+                M::map(res)
+            },
+            M::is_err,
+        )
+        .await
+    }
+
+    /// A snapshot of the metrics of actor(s).
+    ///
+    /// *. Use `Builder::metrics_sink` with `metrics` feature to export metrics as they are
+    /// recorded.
+    pub fn metrics(&self) -> Metrics {
+        let (_, len) = self.tx_subs.load();
+        self.state.metrics().snapshot(
+            len + self.tx.len(),
+            self.state.skipped_count(),
+            self.state.instances(),
+        )
+    }
+
+    /// The state of circuit breaker. None when `Builder::circuit_breaker` is not set.
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.state.breaker().map(|breaker| breaker.state())
+    }
 
-        M::map(res)
+    /// The states of running actor instances sorted by id.
+    pub fn instances(&self) -> Vec<ActorContextState> {
+        self.state.instances()
     }
 
     /// Send a message to actor(s) and ignore the result.
     pub fn do_send(&self, msg: impl Into<A::Message>) {
-        let msg = ContextMessage::Instant(InstantMessage::Static(None, msg.into()));
-        let this = self.tx.clone();
-        runtime::spawn(async move {
-            let _ = this.send(msg).await;
-        });
+        let msg = ContextMessage::instant(InstantMessage::Static(None, msg.into()));
+        self.spawn_deliver(msg, None);
+    }
+
+    /// Send a message to actor(s) and ignore the result like `Address::do_send`.
+    ///
+    /// *. The returned `SendHandle` can be awaited to know if the message is pushed to mailbox.
+    #[must_use = "use `Address::do_send` when the delivery is not needed"]
+    pub fn do_send_tracked(&self, msg: impl Into<A::Message>) -> SendHandle {
+        let (tx, rx) = oneshot_channel();
+        let msg = ContextMessage::instant(InstantMessage::Static(None, msg.into()));
+        self.spawn_deliver(msg, Some(tx));
+        SendHandle { rx }
     }
 
     /// Send a message after a certain amount of delay.
@@ -157,8 +853,9 @@ where
         msg: impl Into<A::Message>,
         delay: Duration,
     ) -> Result<(), ActixSendError> {
+        self.state.throttle().await?;
         let msg = ContextMessage::Delayed(DelayedMessage::Static(msg.into(), delay));
-        self.send_timeout(msg).await?;
+        self.push_control(msg).await?;
         Ok(())
     }
 
@@ -204,26 +901,26 @@ where
     where
         M: Into<A::Message> + MapResult<A::Result> + Clone,
     {
-        let tx_subs = match self.tx_subs.as_ref() {
-            Some(group) => group,
-            None => return vec![Err(ActixSendError::Broadcast)],
-        };
+        if !self.state.allow_broadcast() {
+            return vec![Err(ActixSendError::Broadcast)];
+        }
 
-        tx_subs
-            .as_slice()
-            .iter()
+        self.tx_subs
+            .senders()
+            .into_iter()
             .fold(FuturesUnordered::new(), |fut, sub| {
                 let (tx, rx) = oneshot_channel();
 
                 let msg =
-                    ContextMessage::Instant(InstantMessage::Static(Some(tx), msg.clone().into()));
+                    ContextMessage::instant(InstantMessage::Static(Some(tx), msg.clone().into()));
 
                 let f = async move {
                     let f = sub.send(msg);
                     runtime::timeout(self.state.timeout(), f)
-                        .await?
+                        .await
+                        .map_err(|e| self.observe(e))?
                         .map_err(|_| ActixSendError::Closed)?;
-                    let rx = rx.await.map_err(|_| ActixSendError::Canceled)?;
+                    let rx = rx.await.map_err(|_| ActixSendError::Canceled)??;
                     M::map(rx)
                 };
 
@@ -247,7 +944,7 @@ where
         self.subs
             .as_ref()
             .ok_or(ActixSendError::Subscribe)?
-            .push::<AA, M>(weak)
+            .push::<AA, M>(weak, addr.state.dead_letters().cloned())
             .await;
 
         Ok(())
@@ -284,10 +981,50 @@ where
             .collect::<Vec<Option<Result<(), ActixSendError>>>>()
             .await
             .into_iter()
-            .filter_map(|s| s)
+            .flatten()
             .collect()
     }
 
+    /// Wait for the actor(s) shut down by supervisor when `Builder::max_restarts` is exceeded or
+    /// by `Address::stop`.
+    ///
+    /// Would return the final states of all actor contexts of this address.
+    ///
+    /// *. The future would resolve with an empty report when all actor instances exit in other
+    /// ways. It would never resolve while any of them is running.
+    pub async fn shutdown_report(&self) -> Vec<ActorContextState> {
+        self.state.supervision().wait_report().await
+    }
+
+    /// Stop all actor contexts of this address.
+    ///
+    /// Mailbox would reject new messages with `ActixSendError::Closed` once the stop begins.
+    /// Messages left in mailbox are handled or rejected according to the `StopMode`.
+    ///
+    /// Would return the final states of all actor contexts after their `Actor::on_stop` is called.
+    ///
+    /// *. When `Builder::handle_delayed_on_shutdown` is set all pending delayed messages/futures
+    /// would be pushed to mailbox before the stop begins.
+    ///
+    /// *. It would return right away if all actor instances have already exited. The report is
+    /// empty unless they are shut down by supervisor or a previous stop.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub async fn stop(&self, mode: StopMode) -> Vec<ActorContextState> {
+        if self.state.handle_delay_on_shutdown() {
+            self.state.flush_delayed().await;
+        }
+
+        let deadline = match mode {
+            StopMode::Drain => None,
+            StopMode::Immediate => Some(Instant::now()),
+            StopMode::Deadline(dur) => Some(Instant::now() + dur),
+        };
+
+        self.state.supervision().notify_stop(deadline);
+        self.state.supervision().report_if_idle();
+        self.state.supervision().wait_report().await
+    }
+
     /// Close one actor context for this address.
     ///
     /// Would a return a struct contains the closed context's state.
@@ -295,8 +1032,104 @@ where
     pub async fn close_one(&self) -> Result<ActorContextState, ActixSendError> {
         let (tx, rx) = oneshot_channel();
         let msg = ContextMessage::ManualShutDown(tx);
-        self.send_timeout(msg).await?;
-        Ok(rx.await.map_err(|_| ActixSendError::Canceled)?)
+        self.push_control(msg).await?;
+        rx.await.map_err(|_| ActixSendError::Canceled)
+    }
+}
+
+// mpsc receiver can not be cloned so the mailbox can not be shared by new actor instances.
+#[cfg(not(feature = "actix-runtime-mpsc"))]
+impl<A> Address<A>
+where
+    A: Actor + crate::actor::Handler,
+{
+    /// Spawn more actor instances for this address with the builder function and return their
+    /// ids.
+    ///
+    /// New actor instances share the mailbox with the running ones and have their own mailbox for
+    /// routed, targeted and broadcast messages.
+    ///
+    /// *. `ActixSendError::Closed` would return if the address is stopped or all it's actor
+    /// instances have exited.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub async fn add_instances(&self, num: usize) -> Result<Vec<usize>, ActixSendError> {
+        let (rx, rx_high) = match self.state.mailbox() {
+            Some(mailbox) if !self.tx.is_closed() => mailbox,
+            _ => return Err(ActixSendError::Closed),
+        };
+
+        self.tx_subs.remove_closed();
+
+        let (senders, receivers) = self.state.instance_channels(num);
+
+        let mut ids = Vec::with_capacity(num);
+        for (tx, instance_receiver) in senders.into_iter().zip(receivers) {
+            let id = self.state.next_id();
+            let actor = self.state.build_actor().await;
+
+            self.tx_subs.push(id, tx);
+
+            crate::context::ActorContext::new(
+                id,
+                self.downgrade(),
+                rx.clone(),
+                rx_high.clone(),
+                instance_receiver,
+                actor,
+                self.state.clone(),
+            )
+            .spawn_loop();
+
+            ids.push(id);
+        }
+
+        Ok(ids)
+    }
+
+    /// The latest decisions made by autoscaler. At most 64 decisions are kept.
+    ///
+    /// *. See `Builder::autoscale`.
+    pub fn scale_events(&self) -> Vec<ScaleEvent> {
+        self.state.scale_events()
+    }
+
+    // the number of running actor instances and the messages left in mailbox.
+    pub(crate) fn load(&self) -> Result<(usize, usize), ActixSendError> {
+        if self.tx.is_closed() {
+            return Err(ActixSendError::Closed);
+        }
+        let (num, len) = self.tx_subs.load();
+        Ok((num, len + self.tx.len()))
+    }
+
+    /// Scale the actor instances of this address to the given number.
+    ///
+    /// Missing actor instances are spawned like `Address::add_instances`. Extra actor instances
+    /// with the highest ids are closed after handling the messages in their own mailbox and their
+    /// final states are returned.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub async fn scale_to(&self, num: usize) -> Result<Vec<ActorContextState>, ActixSendError> {
+        assert!(num > 0, "The number of actors must be larger than 0");
+
+        self.tx_subs.remove_closed();
+
+        let mut ids = self.tx_subs.ids();
+        if num > ids.len() {
+            self.add_instances(num - ids.len()).await?;
+            return Ok(Vec::new());
+        }
+
+        ids.sort_unstable();
+
+        let mut states = Vec::with_capacity(ids.len() - num);
+        for id in ids.into_iter().skip(num).rev() {
+            let (tx, rx) = oneshot_channel();
+            self.push_control_to(id, ContextMessage::ManualShutDown(tx))
+                .await?;
+            states.push(rx.await.map_err(|_| ActixSendError::Canceled)?);
+        }
+
+        Ok(states)
     }
 }
 
@@ -322,11 +1155,39 @@ macro_rules! address_run {
 
                 let object = crate::object::FutureObject(f, PhantomData, std::sync::atomic::AtomicPtr::default()).pack();
 
-                let msg = ContextMessage::Instant(InstantMessage::Dynamic(Some(tx), object));
+                let msg = ContextMessage::instant(InstantMessage::Dynamic(Some(tx), object));
+
+                self.guard(async {
+                    self.state.throttle().await?;
+                    self.push_routed(msg, None).await?;
+
+                    rx.await.map_err(|_| ActixSendError::Canceled)??.unpack::<R>().ok_or(ActixSendError::TypeCast)
+                }, Self::no_handler_err)
+                .await
+            }
+
+            /// Run a boxed future on the actor instance with the given id.
+            ///
+            /// *. Errors are the same as `Address::send_to`.
+            #[must_use = "futures do nothing unless you `.await` or poll them"]
+            pub async fn run_on<F, R>(&self, id: usize, f: F) -> Result<R, ActixSendError>
+            where
+                F: FnMut(&mut A) -> Pin<Box<dyn Future<Output = R> $( + $send)* + '_>> + Send + 'static,
+                R: Send + 'static,
+            {
+                let (tx, rx) = oneshot_channel();
+
+                let object = crate::object::FutureObject(f, PhantomData, std::sync::atomic::AtomicPtr::default()).pack();
+
+                let msg = ContextMessage::instant(InstantMessage::Dynamic(Some(tx), object));
 
-                self.send_timeout(msg).await?;
+                self.guard(async {
+                    self.state.throttle().await?;
+                    self.push_to(id, msg).await?;
 
-                rx.await.map_err(|_| ActixSendError::Canceled)?.unpack::<R>().ok_or(ActixSendError::TypeCast)
+                    rx.await.map_err(|_| ActixSendError::Canceled)??.unpack::<R>().ok_or(ActixSendError::TypeCast)
+                }, Self::no_handler_err)
+                .await
             }
 
             /// Run a boxed future and ignore the result.
@@ -335,12 +1196,25 @@ macro_rules! address_run {
                 F: FnMut(&mut A) -> Pin<Box<dyn Future<Output = ()> $( + $send)* + '_>> + Send + 'static,
             {
                 let object = crate::object::FutureObject(f, PhantomData, std::sync::atomic::AtomicPtr::default()).pack();
-                let msg = ContextMessage::Instant(InstantMessage::Dynamic(None, object));
+                let msg = ContextMessage::instant(InstantMessage::Dynamic(None, object));
+
+                self.spawn_deliver(msg, None);
+            }
+
+            /// Run a boxed future and ignore the result like `Address::do_run`.
+            ///
+            /// *. The returned `SendHandle` can be awaited to know if the future is pushed to mailbox.
+            #[must_use = "use `Address::do_run` when the delivery is not needed"]
+            pub fn do_run_tracked<F>(&self, f: F) -> SendHandle
+            where
+                F: FnMut(&mut A) -> Pin<Box<dyn Future<Output = ()> $( + $send)* + '_>> + Send + 'static,
+            {
+                let (tx, rx) = oneshot_channel();
+                let object = crate::object::FutureObject(f, PhantomData, std::sync::atomic::AtomicPtr::default()).pack();
+                let msg = ContextMessage::instant(InstantMessage::Dynamic(None, object));
 
-                let this = self.tx.clone();
-                runtime::spawn(async move {
-                    let _ = this.send(msg).await;
-                });
+                self.spawn_deliver(msg, Some(tx));
+                SendHandle { rx }
             }
 
             /// Run a boxed future after a certain amount of delay.
@@ -355,7 +1229,8 @@ macro_rules! address_run {
 
                 let msg = ContextMessage::Delayed(DelayedMessage::Dynamic(object, delay));
 
-                self.send_timeout(msg).await?;
+                self.state.throttle().await?;
+                self.push_control(msg).await?;
 
                 Ok(())
             }
@@ -380,9 +1255,9 @@ macro_rules! address_run {
 
                 let msg = ContextMessage::Interval(IntervalMessage::Register(tx, object, dur));
 
-                self.send_timeout(msg).await?;
+                self.push_control(msg).await?;
 
-                Ok(rx.await.map_err(|_| ActixSendError::Canceled)?)
+                rx.await.map_err(|_| ActixSendError::Canceled)
             }
         }
     };
@@ -400,14 +1275,56 @@ where
 {
     strong_count: RefCounter<AtomicUsize>,
     tx: WeakSender<ContextMessage<A>>,
-    tx_subs: Option<WeakGroupSender<A>>,
+    tx_subs: WeakGroupSender<A>,
     state: ActorState<A>,
 }
 
+impl<A> Clone for WeakAddress<A>
+where
+    A: Actor,
+{
+    fn clone(&self) -> Self {
+        Self {
+            strong_count: self.strong_count.clone(),
+            tx: self.tx.clone(),
+            tx_subs: self.tx_subs.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
 impl<A> WeakAddress<A>
 where
     A: Actor,
 {
+    // an address of no actor. It can never be upgraded.
+    pub(crate) fn detached(state: ActorState<A>) -> Self
+    where
+        A: 'static,
+    {
+        let (tx, _) = unbounded();
+        Self {
+            strong_count: RefCounter::new(AtomicUsize::new(0)),
+            tx: Sender::from(tx).downgrade(),
+            tx_subs: GroupSender::from(Vec::new()).downgrade(),
+            state,
+        }
+    }
+
+    pub(crate) fn sender(&self) -> &WeakSender<ContextMessage<A>> {
+        &self.tx
+    }
+
+    // remove the sender of an exited actor instance so it would not be routed to anymore.
+    pub(crate) fn remove_instance(&self, id: usize)
+    where
+        A: 'static,
+    {
+        if let Some(group) = self.tx_subs.upgrade() {
+            group.remove(id);
+        }
+    }
+
     pub fn upgrade(&self) -> Option<Address<A>> {
         self.tx.upgrade().map(|sender| {
             self.strong_count.fetch_add(1, Ordering::SeqCst);
@@ -416,8 +1333,8 @@ where
                 tx: sender,
                 tx_subs: self
                     .tx_subs
-                    .as_ref()
-                    .map(|sub| sub.upgrade().expect("Failed to upgrade WeakGroupSender")),
+                    .upgrade()
+                    .expect("Failed to upgrade WeakGroupSender"),
                 subs: None,
                 state: self.state.clone(),
             }
@@ -430,4 +1347,27 @@ where
 pub trait MapResult<M>: Sized {
     type Output;
     fn map(msg: M) -> Result<Self::Output, ActixSendError>;
+
+    /// The priority of message when sending with `Address::send`.
+    fn priority() -> Priority {
+        Priority::Normal
+    }
+
+    /// The hashed key of message for `Router::ConsistentHash`.
+    fn route_key(&self) -> Option<u64> {
+        None
+    }
+
+    /// A copy of message for retrying with `Builder::retry`. None if the message is not
+    /// idempotent.
+    fn retry_copy(&self) -> Option<Self> {
+        None
+    }
+
+    /// Check if the output is an error returned by the handle method of a fallible message.
+    ///
+    /// *. `#[actor_mod]` and `#[handler_v2]` would implement this for messages marked as fallible.
+    fn is_err(_output: &Self::Output) -> bool {
+        false
+    }
 }
@@ -0,0 +1,232 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::time::Duration;
+
+use std::time::Instant;
+
+use crate::actor::{Actor, Handler};
+use crate::address::{Address, WeakAddress};
+use crate::error::ActixSendError;
+use crate::util::runtime;
+
+// The number of latest scale events kept for `Address::scale_events`.
+pub(crate) const MAX_SCALE_EVENTS: usize = 64;
+
+/// The policy of growing and shrinking actor instances of one address with it's load.
+///
+/// The checks are made with `Autoscale::interval`. Every check would grow or shrink at most one
+/// actor instance and the decision is recorded in `Address::scale_events`.
+///
+/// # Example:
+/// ```rust
+/// use std::time::Duration;
+///
+/// use actix_send::prelude::*;
+///
+/// #[actor(no_static)]
+/// pub struct MyActor;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let policy = Autoscale::new(1, 8)
+///         .queue_threshold(64)
+///         .latency_threshold(Duration::from_millis(50))
+///         .idle_timeout(Duration::from_secs(30));
+///
+///     let address: Address<MyActor> = MyActor::builder(|| async { MyActor })
+///         .autoscale(policy)
+///         .start()
+///         .await;
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Autoscale {
+    min: usize,
+    max: usize,
+    queue_threshold: usize,
+    latency_threshold: Option<Duration>,
+    idle_timeout: Duration,
+    interval: Duration,
+}
+
+impl Autoscale {
+    /// Keep the number of actor instances between min and max.(Both inclusive)
+    pub fn new(min: usize, max: usize) -> Self {
+        assert!(min > 0, "The number of actors must be larger than 0");
+        assert!(
+            min <= max,
+            "The min number of actors must not be larger than the max"
+        );
+
+        Self {
+            min,
+            max,
+            queue_threshold: 32,
+            latency_threshold: None,
+            idle_timeout: Duration::from_secs(30),
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Grow one actor instance when the messages left in mailbox exceed the threshold.
+    ///
+    /// Default is 32
+    pub fn queue_threshold(mut self, len: usize) -> Self {
+        self.queue_threshold = len;
+        self
+    }
+
+    /// Grow one actor instance when the average time of handling a message exceeds the threshold.
+    ///
+    /// Default is no threshold.
+    pub fn latency_threshold(mut self, dur: Duration) -> Self {
+        self.latency_threshold = Some(dur);
+        self
+    }
+
+    /// Shrink one actor instance when no message is handled for the duration.
+    ///
+    /// Default is 30 seconds
+    pub fn idle_timeout(mut self, dur: Duration) -> Self {
+        self.idle_timeout = dur;
+        self
+    }
+
+    /// Set the interval of checking the load of actor instances.
+    ///
+    /// Default is 1 second
+    pub fn interval(mut self, dur: Duration) -> Self {
+        self.interval = dur;
+        self
+    }
+
+    pub(crate) fn clamp(&self, num: usize) -> usize {
+        num.max(self.min).min(self.max)
+    }
+}
+
+/// The reason of a decision made by autoscaler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleReason {
+    /// The messages left in mailbox exceed `Autoscale::queue_threshold`.
+    QueueLength(usize),
+    /// The average time of handling a message exceeds `Autoscale::latency_threshold`.
+    Latency(Duration),
+    /// No message is handled for `Autoscale::idle_timeout`.
+    Idle,
+}
+
+/// A decision made by autoscaler.
+#[derive(Clone)]
+pub struct ScaleEvent {
+    from: usize,
+    to: usize,
+    reason: ScaleReason,
+    at: Instant,
+}
+
+impl ScaleEvent {
+    /// The number of actor instances before scaling.
+    pub fn from(&self) -> usize {
+        self.from
+    }
+
+    /// The number of actor instances after scaling.
+    pub fn to(&self) -> usize {
+        self.to
+    }
+
+    /// The reason of scaling.
+    pub fn reason(&self) -> ScaleReason {
+        self.reason
+    }
+
+    /// The time of scaling.
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+}
+
+impl Debug for ScaleEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ScaleEvent")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("reason", &self.reason)
+            .field("at", &self.at)
+            .finish()
+    }
+}
+
+// check the load of actor instances with the interval of policy until the address is gone.
+pub(crate) fn spawn_autoscaler<A>(address: WeakAddress<A>, policy: Autoscale)
+where
+    A: Actor + Handler + 'static,
+{
+    runtime::spawn(async move {
+        // the time actor instances have been idle.
+        let mut idle = Duration::from_secs(0);
+
+        loop {
+            runtime::delay_for(policy.interval).await;
+
+            let address = match address.upgrade() {
+                Some(address) => address,
+                None => return,
+            };
+
+            if check(&address, &policy, &mut idle).await.is_err() {
+                return;
+            }
+        }
+    });
+}
+
+async fn check<A>(
+    address: &Address<A>,
+    policy: &Autoscale,
+    idle: &mut Duration,
+) -> Result<(), ActixSendError>
+where
+    A: Actor + Handler + 'static,
+{
+    let (live, queue) = address.load()?;
+    let (handled, busy) = address.state().take_load();
+
+    match (handled, queue) {
+        (0, 0) => *idle += policy.interval,
+        _ => *idle = Duration::from_secs(0),
+    }
+
+    let latency = match handled {
+        0 => Duration::from_secs(0),
+        handled => busy / handled as u32,
+    };
+
+    let reason = if queue > policy.queue_threshold {
+        Some(ScaleReason::QueueLength(queue))
+    } else if policy.latency_threshold.is_some_and(|t| latency > t) {
+        Some(ScaleReason::Latency(latency))
+    } else {
+        None
+    };
+
+    let (to, reason) = match reason {
+        Some(reason) if live < policy.max => (live + 1, reason),
+        None if *idle >= policy.idle_timeout && live > policy.min => {
+            *idle = Duration::from_secs(0);
+            (live - 1, ScaleReason::Idle)
+        }
+        _ => return Ok(()),
+    };
+
+    address.scale_to(to).await?;
+
+    address.state().push_scale_event(ScaleEvent {
+        from: live,
+        to,
+        reason,
+        at: Instant::now(),
+    });
+
+    Ok(())
+}
@@ -2,12 +2,23 @@ use core::future::Future;
 use core::pin::Pin;
 use core::time::Duration;
 
-use crate::actor::{Actor, ActorState, Handler};
+use crate::actor::{Actor, ActorState, Handler, Mailbox};
 use crate::address::Address;
-use crate::context::{ActorContext, ContextMessage};
+#[cfg(not(feature = "actix-runtime-mpsc"))]
+use crate::autoscale::{spawn_autoscaler, Autoscale};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::context::{overflow, ActorContext, ContextMessage};
+use crate::dead_letter::DeadLetterSink;
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsSink;
+use crate::middleware::Middleware;
+use crate::rate_limit::RateLimit;
 use crate::receiver::Receiver;
+use crate::retry::RetryPolicy;
+use crate::router::Router;
 use crate::sender::Sender;
-use crate::util::channel::unbounded;
+use crate::supervisor::SupervisorStrategy;
+use crate::util::channel::{bounded, unbounded};
 use std::sync::Arc;
 
 pub struct Builder<A>
@@ -18,6 +29,18 @@ where
     pub config: Config,
 }
 
+impl<A> Clone for Builder<A>
+where
+    A: Actor,
+{
+    fn clone(&self) -> Self {
+        Self {
+            actor_builder: self.actor_builder.clone(),
+            config: self.config.clone(),
+        }
+    }
+}
+
 // A container for builder function of actor instance.
 // We box the function into a trait object to make it easier to work with for less type signatures.
 pub struct BuilderFnContainer<A> {
@@ -32,42 +55,96 @@ impl<A> Clone for BuilderFnContainer<A> {
     }
 }
 
-impl<A> BuilderFnContainer<A> {
-    pub(crate) fn new<F, Fut>(f: F) -> Self
-    where
-        F: Fn() -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = A> + 'static,
-    {
-        Self { inner: Arc::new(f) }
-    }
+macro_rules! builder_fn {
+    ($($send:ident)*) => {
+        impl<A> BuilderFnContainer<A> {
+            pub(crate) fn new<F, Fut>(f: F) -> Self
+            where
+                F: Fn() -> Fut + Send + Sync + 'static,
+                Fut: Future<Output = A> $( + $send)* + 'static,
+            {
+                Self { inner: Arc::new(f) }
+            }
 
-    async fn build(&self) -> A {
-        Arc::clone(&self.inner).build().await
-    }
-}
+            pub(crate) async fn build(&self) -> A {
+                Arc::clone(&self.inner).build().await
+            }
+        }
+
+        // A trait would call build method on our actor builder function
+        pub trait BuilderFnTrait<A> {
+            fn build(&self) -> Pin<Box<dyn Future<Output = A> $( + $send)* + '_>>;
+        }
 
-// A trait would call build method on our actor builder function
-pub trait BuilderFnTrait<A> {
-    fn build(&self) -> Pin<Box<dyn Future<Output = A> + '_>>;
+        impl<A, F, Fut> BuilderFnTrait<A> for F
+        where
+            F: Fn() -> Fut + Sync + 'static,
+            Fut: Future<Output = A> $( + $send)*,
+        {
+            fn build(&self) -> Pin<Box<dyn Future<Output = A> $( + $send)* + '_>> {
+                Box::pin(async move { self().await })
+            }
+        }
+    };
 }
 
-impl<A, F, Fut> BuilderFnTrait<A> for F
-where
-    F: Fn() -> Fut + 'static,
-    Fut: Future<Output = A>,
-{
-    fn build(&self) -> Pin<Box<dyn Future<Output = A> + '_>> {
-        Box::pin(async move { self().await })
-    }
+// The builder function would be called again when actor restart inside it's context.
+// So the future it returns must be Send if the context is.
+#[cfg(not(any(feature = "actix-runtime", feature = "actix-runtime-mpsc")))]
+builder_fn!(Send);
+
+#[cfg(any(feature = "actix-runtime", feature = "actix-runtime-mpsc"))]
+builder_fn!();
+
+/// The behavior of a bounded mailbox when it's full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MailboxPolicy {
+    /// Wait for the mailbox to have space. `Builder::timeout` applies to the waiting.
+    Block,
+    /// Return `ActixSendError::Full` to sender immediately.
+    ///
+    /// *. The rejected message goes to `Builder::dead_letters` with `DeadLetterReason::Full` when
+    /// it has no caller waiting for the result.
+    FailFast,
+    /// Drop the oldest message in mailbox to make room for the new one.
+    ///
+    /// *. The caller of the dropped message would get `ActixSendError::Full`. Message without a
+    /// caller goes to `Builder::dead_letters` with `DeadLetterReason::Full`.
+    ///
+    /// *. Control messages like `Address::close_one` and delayed messages are never dropped or
+    /// reordered. They are sent to the high priority lane which falls back to `DropNewest`.
+    ///
+    /// *. `actix-runtime-mpsc` can not evict message from sender side and would fall back to
+    /// `DropNewest`
+    DropOldest,
+    /// Drop the message that is being sent. `ActixSendError::Full` is returned to sender and the
+    /// message goes to `Builder::dead_letters` like `DropOldest`.
+    DropNewest,
 }
 
 #[derive(Clone)]
 pub struct Config {
     pub num: usize,
+    pub mailbox_capacity: Option<usize>,
+    pub mailbox_policy: MailboxPolicy,
     pub restart_on_err: bool,
+    pub supervisor_strategy: SupervisorStrategy,
+    pub max_restarts: Option<(usize, Duration)>,
+    pub restart_backoff: Option<(Duration, Duration)>,
     pub handle_delayed_on_shutdown: bool,
     pub allow_broadcast: bool,
     pub allow_subscribe: bool,
+    pub router: Router,
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub autoscale: Option<Autoscale>,
+    pub metrics: bool,
+    #[cfg(feature = "metrics")]
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+    pub middlewares: Vec<Arc<dyn Middleware>>,
+    pub rate_limit: Option<RateLimit>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+    pub retry: Option<RetryPolicy>,
+    pub dead_letters: Option<Arc<dyn DeadLetterSink>>,
     pub timeout: Duration,
 }
 
@@ -75,22 +152,51 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             num: 1,
+            mailbox_capacity: None,
+            mailbox_policy: MailboxPolicy::Block,
             restart_on_err: false,
+            supervisor_strategy: SupervisorStrategy::OneForOne,
+            max_restarts: None,
+            restart_backoff: None,
             handle_delayed_on_shutdown: false,
             allow_broadcast: false,
             allow_subscribe: false,
+            router: Router::WorkStealing,
+            #[cfg(not(feature = "actix-runtime-mpsc"))]
+            autoscale: None,
+            metrics: false,
+            #[cfg(feature = "metrics")]
+            metrics_sink: None,
+            middlewares: Vec::new(),
+            rate_limit: None,
+            circuit_breaker: None,
+            retry: None,
+            dead_letters: None,
             timeout: Duration::from_secs(10),
         }
     }
 }
 
+impl Config {
+    // keep the number of actors in the range of autoscale policy.
+    fn clamp_num(&mut self) {
+        #[cfg(not(feature = "actix-runtime-mpsc"))]
+        if let Some(policy) = self.autoscale.as_ref() {
+            self.num = policy.clamp(self.num);
+        }
+    }
+}
+
 impl<A> Builder<A>
 where
     A: Actor + Handler + 'static,
 {
     /// Build multiple actors with the num passed.
     ///
-    /// All the actors would steal work from a single `async-channel`.
+    /// All the actors would steal work from a single `async-channel` unless `Builder::router` is
+    /// set.
+    ///
+    /// *. The number of actor instances can be changed at runtime with `Address::scale_to`.
     ///
     /// Default is 1
     pub fn num(mut self, num: usize) -> Self {
@@ -99,8 +205,38 @@ where
         self
     }
 
+    /// Set the capacity of actor(s) mailbox.
+    ///
+    /// Multiple actors of one address share the same mailbox. Every actor instance also has it's
+    /// own mailbox of the capacity for routed, targeted and broadcast messages.
+    ///
+    /// *. The high priority lane of mailbox has the same capacity. See `Priority::High`.
+    ///
+    /// Default is unbounded.
+    pub fn mailbox_capacity(mut self, capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "The capacity of mailbox must be larger than 0"
+        );
+        self.config.mailbox_capacity = Some(capacity);
+        self
+    }
+
+    /// Set the behavior of sending to a full mailbox. Only apply when `Builder::mailbox_capacity`
+    /// is set.
+    ///
+    /// Default is `MailboxPolicy::Block`.
+    pub fn mailbox_policy(mut self, policy: MailboxPolicy) -> Self {
+        self.config.mailbox_policy = policy;
+        self
+    }
+
     /// Notify the actor(s) to handle all delayed messages/futures before it's shutdown.
     ///
+    /// *. With `Address::stop` the delayed messages/futures would be pushed to mailbox and handled
+    /// according to the `StopMode`. `Address::stop` waits at most `Builder::timeout` for them to be
+    /// pushed.
+    ///
     /// Default is false.
     pub fn handle_delayed_on_shutdown(mut self) -> Self {
         self.config.handle_delayed_on_shutdown = true;
@@ -109,12 +245,52 @@ where
 
     /// Notify the actor(s) to restart if it exits on error.
     ///
+    /// A fallible message's handle method returns an error would also rebuild the actor instance
+    /// with the builder function.
+    ///
+    /// A panic when handling a message would rebuild the actor instance in the same way.
+    /// Without restart the panicked actor instance would be stopped.
+    ///
     /// Default is false
     pub fn restart_on_err(mut self) -> Self {
         self.config.restart_on_err = true;
         self
     }
 
+    /// Set the strategy of restarting actor instances when one of them fails.
+    ///
+    /// This would enable `Builder::restart_on_err`.
+    ///
+    /// Default is `SupervisorStrategy::OneForOne`
+    pub fn supervisor_strategy(mut self, strategy: SupervisorStrategy) -> Self {
+        self.config.restart_on_err = true;
+        self.config.supervisor_strategy = strategy;
+        self
+    }
+
+    /// Allow at most `max` restarts within the given duration. Only apply when
+    /// `Builder::restart_on_err` is set.
+    ///
+    /// When the limit is exceeded all actor instances of the address would be shut down and
+    /// `Address::shutdown_report` would resolve with their final states.
+    ///
+    /// Default is no limit.
+    pub fn max_restarts(mut self, max: usize, within: Duration) -> Self {
+        self.config.max_restarts = Some((max, within));
+        self
+    }
+
+    /// Wait before restarting a failed actor instance. The wait starts from `min` and doubles on
+    /// every restart until it reaches `max`. Only apply when `Builder::restart_on_err` is set.
+    ///
+    /// *. Restarts older than the duration of `Builder::max_restarts` would not count.
+    ///
+    /// Default is no backoff.
+    pub fn restart_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.config.restart_backoff = Some((min, max));
+        self
+    }
+
     /// Set the timeout of sending a message.
     ///
     /// Default is 10 seconds
@@ -123,6 +299,115 @@ where
         self
     }
 
+    /// Set the way of dispatching messages to actor instances.
+    ///
+    /// *. Except `Router::WorkStealing` messages sent by `Address::send`, `Address::do_send` and
+    /// `Address::run` are dispatched to the own mailbox of actor instances. Other messages(delayed,
+    /// interval, stream and high priority messages) are still handled by any actor instance.
+    ///
+    /// *. Messages left in the own mailbox of a stopped actor instance are not handled by others.
+    /// Their callers get `ActixSendError::Closed` and the rest go to `Builder::dead_letters`.
+    ///
+    /// Default is `Router::WorkStealing`
+    pub fn router(mut self, router: Router) -> Self {
+        self.config.router = router;
+        self
+    }
+
+    /// Grow and shrink the actor instances with their load following the policy.
+    ///
+    /// *. The number of `Builder::num` would be clamped into the range of the policy.
+    ///
+    /// *. Not available with `actix-runtime-mpsc` as it's mailbox can not be shared by new actor
+    /// instances.
+    ///
+    /// Default is no autoscale.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub fn autoscale(mut self, policy: Autoscale) -> Self {
+        self.config.autoscale = Some(policy);
+        self
+    }
+
+    /// Record the latencies of handling messages. They can be found with `Address::metrics`.
+    ///
+    /// *. Without it `Address::metrics` would only have the queue length, timeouts and canceled
+    /// messages.
+    ///
+    /// Default is false.
+    pub fn metrics(mut self) -> Self {
+        self.config.metrics = true;
+        self
+    }
+
+    /// Export the metrics of actor(s) to the sink.
+    ///
+    /// *. Latencies are recorded as with `Builder::metrics` and can be found with
+    /// `Address::metrics`.
+    ///
+    /// Default is no sink.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_sink(mut self, sink: impl MetricsSink) -> Self {
+        self.config.metrics_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Add a middleware intercepting the messages handled by actor(s).
+    ///
+    /// *. Can be called multiple times. Middlewares are called in the order they are added.
+    ///
+    /// Default is no middleware.
+    pub fn wrap(mut self, middleware: impl Middleware) -> Self {
+        self.config.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Limit the rate of messages sent to actor(s).
+    ///
+    /// *. `Builder::timeout` applies to waiting for the rate limiter. `ActixSendError::RateLimited`
+    /// would return if the wait is longer than it.
+    ///
+    /// *. Messages sent by `Address::broadcast`, `Address::send_stream` and alike are not limited.
+    ///
+    /// Default is no limit.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.config.rate_limit = Some(limit);
+        self
+    }
+
+    /// Fail fast with `ActixSendError::CircuitOpen` when actor(s) keep failing.
+    ///
+    /// *. Only apply to `Address::send`(and alike) and `Address::run`(and alike) that return the
+    /// result of actor(s).
+    ///
+    /// Default is no circuit breaker.
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.config.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Retry sending idempotent messages with `Address::send` and `Address::send_with_priority`
+    /// following the policy.
+    ///
+    /// *. Only messages marked with `#[message(idempotent)]` or `#[idempotent]` are retried. Use
+    /// `Address::send_retry` for other messages implementing `Idempotent`.
+    ///
+    /// Default is no retry.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = Some(policy);
+        self
+    }
+
+    /// Send the messages could not be delivered to actor(s) to the sink.
+    ///
+    /// *. Covers messages sent by `Address::do_send`, delayed messages, messages whose callers have
+    /// gone away and messages failed to reach subscribers of `Address::send_subscribe`.
+    ///
+    /// Default is no sink and the messages are dropped.
+    pub fn dead_letters(mut self, sink: impl DeadLetterSink) -> Self {
+        self.config.dead_letters = Some(Arc::new(sink));
+        self
+    }
+
     /// Allow broadcasting a message to all actor instance of one address.
     ///
     /// Default is false
@@ -140,51 +425,52 @@ where
     }
 
     /// Start actor(s) with the Builder settings.
-    pub async fn start(self) -> Address<A> {
+    pub async fn start(mut self) -> Address<A> {
+        self.config.clamp_num();
         let num = self.config.num;
 
-        let (tx, rx) = unbounded_channel::<ContextMessage<A>>();
+        let (tx, rx, rx_high) = mailbox_channel::<A>(&self.config);
 
-        let state = ActorState::new(self.config);
-        let mut broadcast_senders = Vec::with_capacity(num);
+        #[cfg(not(feature = "actix-runtime-mpsc"))]
+        let autoscale = self.config.autoscale.clone();
 
-        match num {
-            1 => {
-                let actor = self.actor_builder.build().await;
+        let state = ActorState::new(self.config, self.actor_builder.clone());
+        let (instance_senders, instance_receivers) = state.instance_channels(num);
 
-                let broadcast_receiver = state.broadcast_receiver(&mut broadcast_senders, num);
-                ActorContext::new(
-                    0,
-                    tx.downgrade(),
-                    rx,
-                    broadcast_receiver,
-                    actor,
-                    state.clone(),
-                )
-                .spawn_loop();
+        #[cfg(not(feature = "actix-runtime-mpsc"))]
+        state.set_mailbox(&rx, &rx_high);
 
-                Address::new(tx, broadcast_senders.into(), state)
-            }
-            _ => {
-                for i in 0..num {
-                    let actor = self.actor_builder.build().await;
-
-                    let broadcast_receiver = state.broadcast_receiver(&mut broadcast_senders, num);
-
-                    ActorContext::new(
-                        i,
-                        tx.downgrade(),
-                        rx.clone(),
-                        broadcast_receiver,
-                        actor,
-                        state.clone(),
-                    )
-                    .spawn_loop();
-                }
-
-                Address::new(tx, broadcast_senders.into(), state)
-            }
+        let address = Address::new(tx, instance_senders.into(), state.clone());
+
+        // mpsc receiver can not be cloned so we give it to the last actor.
+        let mut rx = Some((rx, rx_high));
+
+        for (i, instance_receiver) in instance_receivers.into_iter().enumerate() {
+            let actor = self.actor_builder.build().await;
+
+            let (rx, rx_high) = match i + 1 == num {
+                true => rx.take().unwrap(),
+                false => rx.as_ref().unwrap().clone(),
+            };
+
+            ActorContext::new(
+                i,
+                address.downgrade(),
+                rx,
+                rx_high,
+                instance_receiver,
+                actor,
+                state.clone(),
+            )
+            .spawn_loop();
         }
+
+        #[cfg(not(feature = "actix-runtime-mpsc"))]
+        if let Some(policy) = autoscale {
+            spawn_autoscaler(address.downgrade(), policy);
+        }
+
+        address
     }
 
     /// Start actors on the given arbiter slice.
@@ -192,77 +478,74 @@ where
     /// Actors would try to spawn evenly on the given arbiters.
     #[cfg(any(feature = "actix-runtime", feature = "actix-runtime-mpsc"))]
     pub async fn start_with_arbiters(
-        self,
+        mut self,
         arbiters: &[actix_rt::Arbiter],
         index: Option<usize>,
     ) -> Address<A> {
+        self.config.clamp_num();
         let num = self.config.num;
 
-        let (tx, rx) = unbounded_channel::<ContextMessage<A>>();
-
-        let state = ActorState::new(self.config);
-        let mut broadcast_senders = Vec::with_capacity(num);
-
-        match num {
-            1 => {
-                let builder = self.actor_builder.clone();
-
-                let index = index.unwrap_or(0);
-
-                arbiters
-                    .get(index)
-                    .expect("Vec<Arbiters> index overflow")
-                    .spawn_fn({
-                        let tx = tx.downgrade();
-                        let state = state.clone();
-                        move || {
-                            actix_rt::spawn(async move {
-                                let actor = builder.build().await;
-                                let ctx = ActorContext::new(0, tx, rx.into(), None, actor, state);
-                                ctx.spawn_loop();
-                            });
-                        }
-                    });
-
-                Address::new(tx, broadcast_senders.into(), state)
-            }
-            _ => {
-                let len = arbiters.len();
-                for i in 0..num {
-                    let index = i % len;
-
-                    let builder = self.actor_builder.clone();
-
-                    let broadcast_receiver = state.broadcast_receiver(&mut broadcast_senders, num);
-
-                    arbiters
-                        .get(index)
-                        .expect("Vec<Arbiters> index overflow")
-                        .spawn_fn({
-                            let tx = tx.downgrade();
-                            let rx = rx.clone();
-                            let state = state.clone();
-                            move || {
-                                actix_rt::spawn(async move {
-                                    let actor = builder.build().await;
-
-                                    ActorContext::new(
-                                        i,
-                                        tx,
-                                        rx.into(),
-                                        broadcast_receiver,
-                                        actor,
-                                        state,
-                                    )
-                                    .spawn_loop();
-                                });
-                            }
+        let (tx, rx, rx_high) = mailbox_channel::<A>(&self.config);
+
+        #[cfg(not(feature = "actix-runtime-mpsc"))]
+        let autoscale = self.config.autoscale.clone();
+
+        let state = ActorState::new(self.config, self.actor_builder.clone());
+        let (instance_senders, instance_receivers) = state.instance_channels(num);
+
+        #[cfg(not(feature = "actix-runtime-mpsc"))]
+        state.set_mailbox(&rx, &rx_high);
+
+        let address = Address::new(tx, instance_senders.into(), state.clone());
+
+        // mpsc receiver can not be cloned so we give it to the last actor.
+        let mut rx = Some((rx, rx_high));
+
+        let len = arbiters.len();
+        for (i, instance_receiver) in instance_receivers.into_iter().enumerate() {
+            let index = match num {
+                1 => index.unwrap_or(0),
+                _ => i % len,
+            };
+
+            let builder = self.actor_builder.clone();
+
+            let (rx, rx_high) = match i + 1 == num {
+                true => rx.take().unwrap(),
+                false => rx.as_ref().unwrap().clone(),
+            };
+
+            arbiters
+                .get(index)
+                .expect("Vec<Arbiters> index overflow")
+                .spawn_fn({
+                    let address = address.downgrade();
+                    let state = state.clone();
+                    move || {
+                        actix_rt::spawn(async move {
+                            let actor = builder.build().await;
+
+                            ActorContext::new(
+                                i,
+                                address,
+                                rx,
+                                rx_high,
+                                instance_receiver,
+                                actor,
+                                state,
+                            )
+                            .spawn_loop();
                         });
-                }
+                    }
+                });
+        }
 
-                Address::new(tx, broadcast_senders.into(), state)
-            }
+        #[cfg(not(feature = "actix-runtime-mpsc"))]
+        if let Some(policy) = autoscale {
+            spawn_autoscaler(address.downgrade(), policy);
         }
+
+        address
     }
 
     fn check_num(num: usize, target: usize) {
@@ -274,8 +557,71 @@ where
     }
 }
 
-fn unbounded_channel<A>() -> (Sender<A>, Receiver<A>) {
-    let (tx, rx) = unbounded::<A>();
+// The mailbox of actor(s). Return the sender and the receivers of normal and high priority lanes.
+#[allow(clippy::type_complexity)]
+fn mailbox_channel<A>(
+    config: &Config,
+) -> (
+    Sender<ContextMessage<A>>,
+    Receiver<ContextMessage<A>>,
+    Receiver<ContextMessage<A>>,
+)
+where
+    A: Actor + 'static,
+{
+    // control messages are sent to the high priority lane so it never evicts them.
+    let policy = match config.mailbox_policy {
+        MailboxPolicy::DropOldest => MailboxPolicy::DropNewest,
+        policy => policy,
+    };
+    let (tx_high, rx_high) = channel_with_policy(config, policy);
+    let (tx, rx) = lane_channel(config);
+
+    (tx.with_high_lane(tx_high), rx, rx_high)
+}
+
+// A channel following the capacity and policy of mailbox.
+fn lane_channel<A>(
+    config: &Config,
+) -> (Sender<ContextMessage<A>>, Receiver<ContextMessage<A>>)
+where
+    A: Actor + 'static,
+{
+    channel_with_policy(config, config.mailbox_policy)
+}
+
+// The mailbox of an actor instance. Control messages are sent to it's high priority lane which
+// never drops them.
+pub(crate) fn instance_channel<A>(config: &Config) -> (Sender<ContextMessage<A>>, Mailbox<A>)
+where
+    A: Actor + 'static,
+{
+    let (tx_high, rx_high) = unbounded();
+    let (tx, rx) = lane_channel(config);
+
+    (tx.with_high_lane(tx_high.into()), (rx, rx_high.into()))
+}
 
-    (tx.into(), rx.into())
+fn channel_with_policy<A>(
+    config: &Config,
+    policy: MailboxPolicy,
+) -> (Sender<ContextMessage<A>>, Receiver<ContextMessage<A>>)
+where
+    A: Actor + 'static,
+{
+    match config.mailbox_capacity {
+        Some(cap) => {
+            let (tx, rx) = bounded(cap);
+            let tx = Sender::from(tx)
+                .with_policy(policy, &rx)
+                .with_overflow(overflow::<A>(config.dead_letters.clone()));
+
+            (tx, rx.into())
+        }
+        None => {
+            let (tx, rx) = unbounded();
+
+            (tx.into(), rx.into())
+        }
+    }
 }
@@ -0,0 +1,299 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::time::Duration;
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::error::ActixSendError;
+use crate::util::smart_pointer::Lock;
+
+/// Fail fast when actor(s) keep failing. Set with `Builder::circuit_breaker`.
+///
+/// *. `ActixSendError::Timeout`, `ActixSendError::Closed` and `ActixSendError::Panicked` returned
+/// by `Address::send`(and alike) are counted as failures. So are the errors returned by the handle
+/// methods of fallible messages.
+///
+/// *. The circuit opens after `CircuitBreaker::threshold` consecutive failures and calls would
+/// return `ActixSendError::CircuitOpen` immediately.
+///
+/// *. After `CircuitBreaker::cool_down` the circuit is half open and
+/// `CircuitBreaker::probes` calls are let through. It closes when all of them succeed and opens
+/// again when any of them fails.
+///
+/// # Example:
+/// ```rust
+/// use std::time::Duration;
+///
+/// use actix_send::prelude::*;
+///
+/// #[actor(no_static)]
+/// pub struct MyActor;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let breaker = CircuitBreaker::new(5)
+///         .cool_down(Duration::from_secs(10))
+///         .on_transition(|from, to| println!("circuit {:?} => {:?}", from, to));
+///
+///     let address: Address<MyActor> = MyActor::builder(|| async { MyActor })
+///         .circuit_breaker(breaker)
+///         .start()
+///         .await;
+///
+///     assert_eq!(address.circuit_state(), Some(CircuitState::Closed));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    threshold: usize,
+    cool_down: Duration,
+    probes: usize,
+    on_transition: Option<Arc<dyn Fn(CircuitState, CircuitState) + Send + Sync>>,
+}
+
+impl CircuitBreaker {
+    /// Open the circuit after the given number of consecutive failures.
+    pub fn new(threshold: usize) -> Self {
+        assert!(
+            threshold > 0,
+            "The threshold of circuit breaker must be larger than 0"
+        );
+
+        Self {
+            threshold,
+            cool_down: Duration::from_secs(10),
+            probes: 1,
+            on_transition: None,
+        }
+    }
+
+    /// Set the duration the circuit stays open before probing actor(s).
+    ///
+    /// Default is 10 seconds
+    pub fn cool_down(mut self, dur: Duration) -> Self {
+        self.cool_down = dur;
+        self
+    }
+
+    /// Set the number of calls let through when the circuit is half open.
+    ///
+    /// Default is 1
+    pub fn probes(mut self, num: usize) -> Self {
+        assert!(num > 0, "The number of probes must be larger than 0");
+        self.probes = num;
+        self
+    }
+
+    /// Call the function with the previous and the current state when the circuit changes it's
+    /// state.
+    ///
+    /// *. The function is called on the hot path of callers so it should return quickly.
+    pub fn on_transition<F>(mut self, f: F) -> Self
+    where
+        F: Fn(CircuitState, CircuitState) + Send + Sync + 'static,
+    {
+        self.on_transition = Some(Arc::new(f));
+        self
+    }
+}
+
+impl Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("CircuitBreaker")
+            .field("threshold", &self.threshold)
+            .field("cool_down", &self.cool_down)
+            .field("probes", &self.probes)
+            .finish()
+    }
+}
+
+/// The state of circuit breaker. See `Address::circuit_state`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls are let through and failures are counted.
+    Closed,
+    /// Calls fail fast with `ActixSendError::CircuitOpen`.
+    Open,
+    /// Probe calls are let through to check if actor(s) recovered.
+    HalfOpen,
+}
+
+pub(crate) struct Breaker {
+    policy: CircuitBreaker,
+    inner: Lock<BreakerInner>,
+}
+
+struct BreakerInner {
+    state: CircuitState,
+    // consecutive failures when closed.
+    failures: usize,
+    opened_at: Instant,
+    // probes let through and succeeded when half open.
+    probing: usize,
+    succeeded: usize,
+}
+
+impl Breaker {
+    pub(crate) fn new(policy: CircuitBreaker) -> Self {
+        Self {
+            policy,
+            inner: Lock::new(BreakerInner {
+                state: CircuitState::Closed,
+                failures: 0,
+                opened_at: Instant::now(),
+                probing: 0,
+                succeeded: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn state(&self) -> CircuitState {
+        let mut inner = self.inner.lock();
+        let transition = self.try_half_open(&mut inner);
+        let state = inner.state;
+        drop(inner);
+
+        self.notify(transition);
+        state
+    }
+
+    // take a permit for a call or fail fast when the circuit is open.
+    pub(crate) fn acquire(self: &Arc<Self>) -> Result<Permit, ActixSendError> {
+        let mut inner = self.inner.lock();
+        let transition = self.try_half_open(&mut inner);
+
+        let res = match inner.state {
+            CircuitState::Closed => Ok(Permit::new(self.clone(), false)),
+            CircuitState::HalfOpen if inner.probing < self.policy.probes => {
+                inner.probing += 1;
+                Ok(Permit::new(self.clone(), true))
+            }
+            _ => Err(ActixSendError::CircuitOpen),
+        };
+        drop(inner);
+
+        self.notify(transition);
+        res
+    }
+
+    fn record(&self, probe: bool, failed: bool) {
+        let mut inner = self.inner.lock();
+
+        let transition = match (inner.state, probe) {
+            (CircuitState::Closed, false) if failed => {
+                inner.failures += 1;
+                match inner.failures >= self.policy.threshold {
+                    true => self.open(&mut inner),
+                    false => None,
+                }
+            }
+            (CircuitState::Closed, false) => {
+                inner.failures = 0;
+                None
+            }
+            (CircuitState::HalfOpen, true) if failed => self.open(&mut inner),
+            (CircuitState::HalfOpen, true) => {
+                inner.succeeded += 1;
+                match inner.succeeded >= self.policy.probes {
+                    true => {
+                        inner.state = CircuitState::Closed;
+                        inner.failures = 0;
+                        Some((CircuitState::HalfOpen, CircuitState::Closed))
+                    }
+                    false => None,
+                }
+            }
+            // results of calls made before the last transition are ignored.
+            _ => None,
+        };
+        drop(inner);
+
+        self.notify(transition);
+    }
+
+    // give back the probe slot of a call that is dropped before finishing.
+    fn release(&self) {
+        let mut inner = self.inner.lock();
+        if inner.state == CircuitState::HalfOpen {
+            inner.probing = inner.probing.saturating_sub(1);
+        }
+    }
+
+    fn open(&self, inner: &mut BreakerInner) -> Option<(CircuitState, CircuitState)> {
+        let from = inner.state;
+        inner.state = CircuitState::Open;
+        inner.opened_at = Instant::now();
+        Some((from, CircuitState::Open))
+    }
+
+    fn try_half_open(&self, inner: &mut BreakerInner) -> Option<(CircuitState, CircuitState)> {
+        if inner.state == CircuitState::Open && inner.opened_at.elapsed() >= self.policy.cool_down {
+            inner.state = CircuitState::HalfOpen;
+            inner.probing = 0;
+            inner.succeeded = 0;
+            return Some((CircuitState::Open, CircuitState::HalfOpen));
+        }
+        None
+    }
+
+    fn notify(&self, transition: Option<(CircuitState, CircuitState)>) {
+        if let (Some((from, to)), Some(f)) = (transition, self.policy.on_transition.as_ref()) {
+            f(from, to);
+        }
+    }
+}
+
+// A call let through by circuit breaker. The result of call must be recorded with it.
+pub(crate) struct Permit {
+    breaker: Arc<Breaker>,
+    probe: bool,
+    recorded: bool,
+}
+
+impl Permit {
+    fn new(breaker: Arc<Breaker>, probe: bool) -> Self {
+        Self {
+            breaker,
+            probe,
+            recorded: false,
+        }
+    }
+
+    // record the result of call. is_err checks if the handler returned an error.
+    pub(crate) fn record<R>(self, res: &Result<R, ActixSendError>, is_err: fn(&R) -> bool) {
+        match res {
+            Ok(res) => {
+                let failed = is_err(res);
+                self.finish(failed)
+            }
+            Err(e) => self.record_err(e),
+        }
+    }
+
+    // record a call failed before the handler returned.
+    pub(crate) fn record_err(self, e: &ActixSendError) {
+        self.finish(is_failure(e))
+    }
+
+    fn finish(mut self, failed: bool) {
+        self.recorded = true;
+        self.breaker.record(self.probe, failed);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.probe && !self.recorded {
+            self.breaker.release();
+        }
+    }
+}
+
+// errors counted as failures of actor(s). Errors like `ActixSendError::Full` and
+// `ActixSendError::RateLimited` are caused by callers.
+pub(crate) fn is_failure(e: &ActixSendError) -> bool {
+    matches!(
+        e,
+        ActixSendError::Timeout | ActixSendError::Closed | ActixSendError::Panicked
+    )
+}
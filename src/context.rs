@@ -1,18 +1,36 @@
 use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
 use core::time::Duration;
 
-use futures_util::StreamExt;
-
-use crate::actor::{Actor, ActorState, Handler};
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures_util::future::{select, Either};
+use futures_util::{pin_mut, FutureExt, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::actor::{Actor, ActorState, Handler, Mailbox};
+use crate::address::WeakAddress;
+use crate::builder::{BuilderFnContainer, Config};
+use crate::dead_letter::{DeadLetter, DeadLetterReason, DeadLetterSink};
+use crate::envelope::Envelope;
+use crate::error::ActixSendError;
+use crate::metrics::Recorder;
+use crate::middleware::{self, HandleOutcome, MessageInfo};
 use crate::object::{AnyObjectContainer, FutureObjectContainer};
 use crate::receiver::Receiver;
-use crate::sender::WeakSender;
+use crate::sender::{Overflow, WeakSender};
+use crate::supervisor::Signal;
 use crate::util::{
     channel::OneShotSender,
     future_handle::{spawn_cancelable, FutureHandler},
     runtime,
+    span::{instrument, Span},
 };
-use futures_util::stream::{select, Select};
+use futures_util::stream::{self, Select};
 
 // ActorContext would hold actor instance local state and the actor itself.
 // State shared by actors are stored in ActorState.
@@ -20,12 +38,22 @@ pub(crate) struct ActorContext<A>
 where
     A: Actor + Handler + 'static,
 {
-    id: usize,
-    generation: usize,
-    tx: WeakSender<ContextMessage<A>>,
-    rx: Option<Recv<A>>,
-    selector: Option<Select<Recv<A>, Recv<A>>>,
-    manual_shutdown: bool,
+    ctx: Context<A>,
+    // select from the high priority lanes of the shared mailbox and the mailbox of this actor
+    // instance. They are always polled before other receivers.
+    rx_high: Select<Recv<A>, Recv<A>>,
+    // select from the shared mailbox and the mailbox of this actor instance.
+    selector: Select<Recv<A>, Recv<A>>,
+    // Actor::on_stop has run for the actor and it's not rebuilt yet.
+    stopped: bool,
+    // Some when actor is stopping by `Address::stop` or supervisor.
+    // Messages left in mailbox would be handled before the deadline.
+    stop: Option<Option<Instant>>,
+    // Some when actor instance is closed by `Address::close_one` or `Address::scale_to`.
+    // The state is returned after handling the messages left in it's own mailbox.
+    shutdown: Option<OneShotSender<ActorContextState>>,
+    signal: broadcast::Receiver<Signal>,
+    recorder: Recorder,
     actor: A,
     state: ActorState<A>,
 }
@@ -38,39 +66,120 @@ where
 {
     pub(crate) fn new(
         id: usize,
-        tx: WeakSender<ContextMessage<A>>,
+        address: WeakAddress<A>,
         rx: Receiver<ContextMessage<A>>,
-        broadcast_receiver: Option<Receiver<ContextMessage<A>>>,
+        rx_high: Receiver<ContextMessage<A>>,
+        instance_receiver: Mailbox<A>,
         actor: A,
         state: ActorState<A>,
     ) -> Self {
-        // When rx_sub is Some we take both rx and rx_sub and construct selector field.
-        let (rx, selector) = match broadcast_receiver {
-            Some(broadcast_receiver) => (None, Some(select(rx, broadcast_receiver))),
-            None => (Some(rx), None),
-        };
-
         Self {
-            id,
-            generation: 0,
-            tx,
-            rx,
-            selector,
-            manual_shutdown: false,
+            ctx: Context {
+                id,
+                generation: 0,
+                address,
+                state: state.clone(),
+                stop: false,
+                children: Vec::new(),
+                envelope: None,
+            },
+            rx_high: stream::select(rx_high, instance_receiver.1),
+            selector: stream::select(rx, instance_receiver.0),
+            stopped: false,
+            stop: None,
+            shutdown: None,
+            signal: state.supervision().signal_receiver(),
+            recorder: state.metrics().recorder(id),
             actor,
             state,
         }
     }
 
+    // wait for the next message from receivers or signal from supervisor.
+    // signal is polled first so restart and shutdown are not blocked by messages.
+    async fn next(&mut self) -> Option<Event<A>> {
+        {
+            let msg = recv(&mut self.rx_high, &mut self.selector);
+            let signal = self.signal.recv();
+            pin_mut!(msg, signal);
+
+            match select(signal, msg).await {
+                Either::Left((Ok(signal), _)) => return Some(Event::Signal(signal)),
+                Either::Left((Err(RecvError::Lagged(_)), _)) => {}
+                Either::Left((Err(RecvError::Closed), msg)) => {
+                    return msg.await.map(Event::Message)
+                }
+                Either::Right((msg, _)) => return msg.map(Event::Message),
+            }
+        }
+
+        // signals are missed. Stop if the address is stopping or restart in case the missed
+        // signals include a restart of this actor instance.
+        let signal = match self.state.supervision().stopped() {
+            Some(deadline) => Signal::Stop(deadline),
+            None => Signal::Restart {
+                from: self.ctx.id,
+                except: usize::MAX,
+            },
+        };
+        Some(Event::Signal(signal))
+    }
+
+    // return true if we want to break the streaming loop
+    async fn handle_signal(&mut self, signal: Signal) -> bool {
+        match signal {
+            Signal::Restart { from, except } => {
+                if self.ctx.id >= from && self.ctx.id != except && !self.restart().await {
+                    return self.escalate();
+                }
+                false
+            }
+            Signal::Stop(deadline) => {
+                self.stop = Some(deadline);
+                true
+            }
+        }
+    }
+
+    // close the mailbox and handle the messages left in it until the deadline.
+    async fn drain(&mut self, deadline: Option<Instant>) {
+        let (rx_high, instance_high) = self.rx_high.get_mut();
+        rx_high.close();
+        instance_high.close();
+        let (rx, instance_receiver) = self.selector.get_mut();
+        rx.close();
+        instance_receiver.close();
+
+        while let Some(msg) = recv(&mut self.rx_high, &mut self.selector).await {
+            match deadline {
+                Some(deadline) if Instant::now() >= deadline => reject(&self.state, msg),
+                _ => {
+                    let _ = self.handle_msg(msg).await;
+                }
+            }
+        }
+    }
+
     // return true if we want to break the streaming loop
     async fn handle_msg(&mut self, msg: ContextMessage<A>) -> bool {
         match msg {
-            ContextMessage::Instant(msg) => self.handle_instant_msg(msg).await,
+            ContextMessage::Instant(msg, envelope) => {
+                let outcome = self.handle_instant_msg(msg, envelope).await;
+                // actor stops itself with Context::stop.
+                if self.ctx.stop {
+                    return true;
+                }
+                return self.supervise(outcome).await;
+            }
             ContextMessage::Delayed(msg) => self.handle_delayed_msg(msg),
-            ContextMessage::Interval(msg) => self.handle_interval_msg(msg).await,
+            ContextMessage::Interval(msg) => {
+                let outcome = self.handle_interval_msg(msg).await;
+                return self.supervise(outcome).await;
+            }
+            // keep running if the caller is gone.
             ContextMessage::ManualShutDown(tx) => {
-                if tx.send(self.state()).is_ok() {
-                    self.manual_shutdown = true;
+                if !tx.is_closed() {
+                    self.shutdown = Some(tx);
                     return true;
                 }
             }
@@ -79,150 +188,722 @@ where
         false
     }
 
-    async fn handle_instant_msg(&mut self, msg: InstantMessage<A>) {
+    async fn handle_instant_msg(
+        &mut self,
+        msg: InstantMessage<A>,
+        envelope: Option<Envelope>,
+    ) -> Outcome {
+        let (actor, id) = (core::any::type_name::<A>(), self.ctx.id);
+        let expired = matches!(&envelope, Some(envelope) if envelope.is_expired());
+        let span = self.ctx.set_envelope(envelope);
+
         match msg {
             InstantMessage::Static(tx, msg) => {
-                let res = self.actor.handle(msg).await;
+                let canceled = is_canceled(&tx);
+                let (res, outcome) = self.handle_static(msg, canceled, expired, &span).await;
                 if let Some(tx) = tx {
                     let _ = tx.send(res);
                 }
+                outcome
+            }
+            InstantMessage::Batch(tx, msgs) => {
+                let mut results = Vec::with_capacity(msgs.len());
+                let mut outcome = Outcome::Ok;
+                for msg in msgs {
+                    // the rest of batch is not handled after a panic as actor state can not be
+                    // trusted.
+                    if let Outcome::Panic = outcome {
+                        results.push(Err(ActixSendError::Closed));
+                        continue;
+                    }
+                    let canceled = tx.is_closed();
+                    let (res, o) = self.handle_static(msg, canceled, expired, &span).await;
+                    results.push(res);
+                    outcome = outcome.max(o);
+                }
+                let _ = tx.send(Ok(results));
+                outcome
             }
             InstantMessage::Dynamic(tx, mut fut) => {
-                let res = fut.handle(&mut self.actor).await;
+                if is_canceled(&tx) || expired {
+                    return self.skip(tx);
+                }
+                if let Err(e) = self.before("run") {
+                    return self.refuse(tx, e);
+                }
+                let start = self.state.is_timed().then(Instant::now);
+                let fut = fut.handle(&mut self.actor);
+                let res = catch_unwind(instrument(fut, &span, actor, "run", id)).await;
+                let outcome = Outcome::from_result(&res, |_| false);
+                self.finish("run", &outcome, start);
                 if let Some(tx) = tx {
                     let _ = tx.send(res);
                 }
+                outcome
             }
         }
     }
 
+    // handle a message sent by user and return the result for the caller.
+    async fn handle_static(
+        &mut self,
+        msg: A::Message,
+        canceled: bool,
+        expired: bool,
+        span: &Span,
+    ) -> (Result<A::Result, ActixSendError>, Outcome) {
+        let (actor, id) = (core::any::type_name::<A>(), self.ctx.id);
+
+        // skip the message if the caller has gone away or the deadline is reached.
+        // The caller would get a timeout if it's still waiting.
+        if (canceled || expired) && !A::always_run(&msg) {
+            if canceled {
+                self.state
+                    .dead_letter_message(msg, DeadLetterReason::Canceled);
+            }
+            self.state.inc_skipped();
+            return (Err(ActixSendError::Timeout), Outcome::Ok);
+        }
+        let name = A::message_name(&msg);
+        if let Err(e) = self.before(name) {
+            return (Err(e), Outcome::Ok);
+        }
+        let start = self.state.is_timed().then(Instant::now);
+        let fut = self.actor.handle_with_context(msg, &mut self.ctx);
+        let res = catch_unwind(instrument(fut, span, actor, name, id)).await;
+        let outcome = Outcome::from_result(&res, A::is_err);
+        self.finish(name, &outcome, start);
+        (res, outcome)
+    }
+
+    // skip a message. The caller would get a timeout if it's still waiting.
+    fn skip<R>(&self, tx: Option<OneShotSender<Result<R, ActixSendError>>>) -> Outcome {
+        self.state.inc_skipped();
+        if let Some(tx) = tx {
+            let _ = tx.send(Err(ActixSendError::Timeout));
+        }
+        Outcome::Ok
+    }
+
+    // refuse a message rejected by middleware. The caller would get the error.
+    fn refuse<R>(
+        &self,
+        tx: Option<OneShotSender<Result<R, ActixSendError>>>,
+        e: ActixSendError,
+    ) -> Outcome {
+        if let Some(tx) = tx {
+            let _ = tx.send(Err(e));
+        }
+        Outcome::Ok
+    }
+
+    // call the before hooks of middlewares.
+    fn before(&mut self, message: &'static str) -> Result<(), ActixSendError> {
+        let middlewares = self.state.middlewares();
+        if middlewares.is_empty() {
+            return Ok(());
+        }
+
+        let id = self.ctx.id;
+        let info = MessageInfo::new(
+            core::any::type_name::<A>(),
+            message,
+            id,
+            self.ctx.envelope(),
+        );
+        middleware::before(middlewares, &info)
+    }
+
+    // record the latency of a handled message and notify middlewares.
+    // The message is not timed when there is nothing to record or notify.
+    fn finish(&mut self, message: &'static str, outcome: &Outcome, start: Option<Instant>) {
+        let dur = match start {
+            Some(start) => start.elapsed(),
+            None => return,
+        };
+
+        self.state.record_handled(dur);
+        if self.state.metrics().is_enabled() {
+            self.recorder
+                .record(self.state.metrics(), self.ctx.id, message, dur);
+        }
+
+        let middlewares = self.state.middlewares();
+        if !middlewares.is_empty() {
+            let id = self.ctx.id;
+            let info = MessageInfo::new(
+                core::any::type_name::<A>(),
+                message,
+                id,
+                self.ctx.envelope(),
+            );
+            middleware::after(middlewares, &info, outcome.into(), dur);
+        }
+    }
+
+    // act on the outcome of handling a message.
+    // return true if we want to break the streaming loop
+    async fn supervise(&mut self, outcome: Outcome) -> bool {
+        match outcome {
+            Outcome::Ok => false,
+            Outcome::Err | Outcome::Panic if self.state.restart_on_err() => {
+                match self.state.supervision().record_restart() {
+                    Some(delay) => {
+                        if delay > Duration::from_secs(0) {
+                            runtime::delay_for(delay).await;
+                        }
+                        if !self.restart().await {
+                            return self.escalate();
+                        }
+                        self.state.supervision().notify_restart(self.ctx.id);
+                        false
+                    }
+                    None => self.escalate(),
+                }
+            }
+            Outcome::Err => false,
+            // actor state can not be trusted after a panic so we stop it.
+            Outcome::Panic => {
+                self.state.supervision().fail();
+                true
+            }
+        }
+    }
+
+    // rebuild the actor instance with the builder function and bump the generation.
+    // A panic from the builder function or Actor::on_start is a failed restart and counts against
+    // the restart intensity. return false when the intensity is exceeded.
+    async fn restart(&mut self) -> bool {
+        self.ctx.cancel_children();
+        self.stop_actor().await;
+        loop {
+            if let Ok(actor) = catch_unwind(self.state.build_actor()).await {
+                self.actor = actor;
+                self.stopped = false;
+                self.ctx.generation += 1;
+                if catch_unwind(self.actor.on_start()).await.is_ok() {
+                    break;
+                }
+            }
+
+            match self.state.supervision().record_restart() {
+                Some(delay) if delay > Duration::from_secs(0) => {
+                    runtime::delay_for(delay).await;
+                }
+                Some(_) => {}
+                None => return false,
+            }
+        }
+        self.state.register_instance(self.state());
+        self.state
+            .metrics()
+            .restarted(self.ctx.id, self.ctx.generation);
+        true
+    }
+
+    // run Actor::on_stop once for the current actor.
+    async fn stop_actor(&mut self) {
+        if !self.stopped {
+            self.stopped = true;
+            let _ = catch_unwind(self.actor.on_stop()).await;
+        }
+    }
+
+    // the restart intensity is exceeded. Stop all actor instances of the address.
+    // return true so the streaming loop breaks.
+    fn escalate(&mut self) -> bool {
+        let now = Instant::now();
+        self.state.supervision().notify_stop(Some(now));
+        self.stop = Some(Some(now));
+        true
+    }
+
     fn handle_delayed_msg(&self, msg: DelayedMessage<A>) {
         let (msg, dur) = match msg {
             DelayedMessage::Static(msg, dur) => (
-                ContextMessage::Instant(InstantMessage::Static(None, msg)),
+                ContextMessage::instant(InstantMessage::Static(None, msg)),
                 dur,
             ),
             DelayedMessage::Dynamic(fut, dur) => (
-                ContextMessage::Instant(InstantMessage::Dynamic(None, fut)),
+                ContextMessage::instant(InstantMessage::Dynamic(None, fut)),
                 dur,
             ),
         };
 
-        if let Some(tx) = self.tx.upgrade() {
-            let handle_delay_on_shutdown = self.state.handle_delay_on_shutdown();
-
-            let handler = spawn_cancelable(runtime::delay_for(dur), move |either| async move {
-                if let futures_util::future::Either::Left(_) = either {
-                    if !handle_delay_on_shutdown {
-                        return;
-                    }
-                }
-                let _ = tx.send(msg).await;
-            });
-
-            self.state.push_handler(vec![handler]);
-        }
+        schedule_delayed(&self.state, self.ctx.address.sender(), msg, dur);
     }
 
-    async fn handle_interval_msg(&mut self, msg: IntervalMessage<A>) {
+    async fn handle_interval_msg(&mut self, msg: IntervalMessage<A>) -> Outcome {
         match msg {
             IntervalMessage::Run(idx) => {
                 let mut guard = self.state.interval_futures.lock().await;
                 if let Some(fut) = guard.get_mut(&idx) {
-                    let _ = fut.handle(&mut self.actor).await;
+                    let res = catch_unwind(fut.handle(&mut self.actor)).await;
+                    return Outcome::from_result(&res, |_| false);
                 }
             }
             IntervalMessage::Remove(idx) => {
                 let _ = self.state.interval_futures.remove(idx).await;
             }
             IntervalMessage::Register(tx, interval_future, dur) => {
-                // insert interval future to context and get it's index
-                let index = self.state.interval_futures.insert(interval_future).await;
-
-                // construct the interval future
-                let mut interval = runtime::interval(dur);
-                let ctx_tx = self.tx.clone();
-                let interval_loop = Box::pin(async move {
-                    loop {
-                        let _ = runtime::tick(&mut interval).await;
-                        match ctx_tx.upgrade() {
-                            Some(tx) => {
-                                let _ = tx
-                                    .send(ContextMessage::Interval(IntervalMessage::Run(index)))
-                                    .await;
-                            }
-                            None => break,
-                        }
-                        runtime::yield_now().await;
-                    }
-                });
-
-                // spawn a cancelable future and use the handler to execute the cancellation.
-                let mut interval_handler = spawn_cancelable(interval_loop, |_| async {});
-
-                // we attach the index of interval future and a tx of our channel to handler.
-                interval_handler.attach_tx(index, self.tx.clone());
-
-                self.state.push_handler(vec![interval_handler.clone()]);
+                let interval_handler =
+                    register_interval(&self.state, self.ctx.address.sender(), interval_future, dur)
+                        .await;
 
                 let _ = tx.send(interval_handler);
             }
         }
+
+        Outcome::Ok
     }
 
     pub(crate) fn spawn_loop(mut self) {
         runtime::spawn(async {
             self.actor.on_start().await;
             self.state.inc_active();
+            self.state.register_instance(self.state());
 
-            match self.selector.is_some() {
-                true => {
-                    while let Some(msg) = self.selector.as_mut().unwrap().next().await {
-                        let should_break = self.handle_msg(msg).await;
+            // the address could be stopped before this actor instance subscribed to signal.
+            self.stop = self.state.supervision().stopped();
 
-                        if should_break {
-                            break;
-                        }
+            while self.stop.is_none() {
+                let should_break = match self.next().await {
+                    Some(Event::Message(msg)) => self.handle_msg(msg).await,
+                    Some(Event::Signal(signal)) => self.handle_signal(signal).await,
+                    None => true,
+                };
 
-                        runtime::yield_now().await;
-                    }
+                if should_break {
+                    break;
                 }
-                false => {
-                    while let Ok(msg) = self.rx.as_mut().unwrap().recv().await {
-                        let should_break = self.handle_msg(msg).await;
 
-                        if should_break {
-                            break;
-                        }
+                runtime::yield_now().await;
+            }
 
-                        runtime::yield_now().await;
-                    }
-                }
-            };
+            self.exit().await;
+        });
+    }
 
-            // dec_active will return false if the actors are already shutdown.
-            if self.state.dec_active() && self.state.restart_on_err() && !self.manual_shutdown {
-                self.generation += 1;
-                return self.spawn_loop();
-            };
+    // every actor instance exits through here so it's removed from the address and counted out
+    // once however it stops.
+    async fn exit(mut self) {
+        // messages left in mailbox are handled until the deadline of stop.
+        if let Some(deadline) = self.stop {
+            self.drain(deadline).await;
+        }
 
-            self.actor.on_stop().await;
-        });
+        if let Some(tx) = self.shutdown.take() {
+            self.drain_own().await;
+            let _ = tx.send(self.state());
+        }
+
+        self.state.remove_instance(self.ctx.id);
+        self.ctx.address.remove_instance(self.ctx.id);
+        self.reject_own().await;
+        self.ctx.cancel_children();
+        self.stop_actor().await;
+
+        if self.state.exit(self.state(), self.stop.is_some()) {
+            self.state.release_mailbox();
+            // close the shared mailbox and reject the messages left in it.
+            // A sender could still hold a receiver for MailboxPolicy::DropOldest so the
+            // mailbox would not close by itself.
+            self.drain(Some(Instant::now())).await;
+        }
+
+        // Address::stop could be waiting for this actor instance. The context is dropped first
+        // so it's not counted as alive.
+        let state = self.state.clone();
+        drop(self);
+        state.supervision().report_if_idle();
+    }
+
+    // close the mailbox of this actor instance and handle the messages left in it.
+    async fn drain_own(&mut self) {
+        let (_, instance_receiver) = self.selector.get_mut();
+        instance_receiver.close();
+        while let Some(msg) = self.selector.get_mut().1.next().await {
+            let _ = self.handle_msg(msg).await;
+        }
+    }
+
+    // close the mailbox of this actor instance and reject the messages left in it.
+    // Their callers get ActixSendError::Closed and the rest go to the dead letter sink.
+    async fn reject_own(&mut self) {
+        let (_, instance_high) = self.rx_high.get_mut();
+        let (_, instance_receiver) = self.selector.get_mut();
+        instance_high.close();
+        instance_receiver.close();
+        let mut own = stream::select(instance_high, instance_receiver);
+        while let Some(msg) = own.next().await {
+            reject(&self.state, msg);
+        }
     }
 
     fn state(&self) -> ActorContextState {
         ActorContextState {
-            id: self.id,
-            generation: self.generation,
+            id: self.ctx.id,
+            generation: self.ctx.generation,
         }
     }
 }
 
+/// The context of an actor instance. It's passed to `Handler::handle` with the message.
+///
+/// *. `#[handler_v2]` and `#[actor_mod]` would pass it to handle methods having a
+/// `ctx: &mut Context<Self>` argument.
+pub struct Context<A>
+where
+    A: Actor + 'static,
+{
+    id: usize,
+    generation: usize,
+    address: WeakAddress<A>,
+    state: ActorState<A>,
+    stop: bool,
+    // futures spawned by actor. They are canceled when the actor instance stops or restarts.
+    children: Vec<FutureHandler<A>>,
+    // the envelope of the message being handled. It's created on demand for messages sent
+    // without an envelope.
+    envelope: Option<Envelope>,
+}
+
+impl<A> Context<A>
+where
+    A: Actor,
+{
+    /// A context not attached to any actor instance.
+    ///
+    /// `Handler::handle` generated by `#[actor_mod]` and `#[handler_v2]` passes it to handle
+    /// methods taking `&mut Context<Self>` when the message is not handled by an actor instance.
+    ///
+    /// *. It's address can not be upgraded and futures scheduled with it would never run.
+    #[doc(hidden)]
+    pub fn detached() -> Self {
+        let builder = BuilderFnContainer::new(core::future::pending::<A>);
+        let state = ActorState::new(Config::default(), builder);
+
+        Self {
+            id: 0,
+            generation: 0,
+            address: WeakAddress::detached(state.clone()),
+            state,
+            stop: false,
+            children: Vec::new(),
+            envelope: None,
+        }
+    }
+
+    /// The envelope of the message being handled.
+    ///
+    /// *. A message sent without an envelope gets a new one when it's asked for the first time.
+    pub fn envelope(&mut self) -> &Envelope {
+        self.envelope.get_or_insert_with(Envelope::new)
+    }
+
+    /// The id of actor context.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The times actor instance restarted.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// A weak address of the actor(s). It would not keep the actor(s) alive.
+    pub fn address(&self) -> &WeakAddress<A> {
+        &self.address
+    }
+
+    /// Stop the actor instance after handling the current message.
+    pub fn stop(&mut self) {
+        self.stop = true;
+    }
+
+    // replace the envelope with the one of next message and return the span of it's caller.
+    fn set_envelope(&mut self, mut envelope: Option<Envelope>) -> Span {
+        let span = envelope
+            .as_mut()
+            .map(Envelope::take_span)
+            .unwrap_or_else(Span::none);
+        self.envelope = envelope;
+        span
+    }
+
+    fn cancel_children(&mut self) {
+        for child in self.children.drain(..) {
+            child.cancel();
+        }
+    }
+}
+
+macro_rules! context_run {
+    ($($send:ident)*) => {
+        impl<A> Context<A>
+        where
+            A: Actor,
+        {
+            /// Run a boxed future on actor(s) after a certain amount of delay.
+            pub fn run_later<F>(&self, delay: Duration, f: F)
+            where
+                F: FnMut(&mut A) -> Pin<Box<dyn Future<Output = ()> $( + $send)* + '_>> + Send + 'static,
+            {
+                let object = crate::object::FutureObject(f, PhantomData, std::sync::atomic::AtomicPtr::default()).pack();
+                let msg = ContextMessage::instant(InstantMessage::Dynamic(None, object));
+
+                schedule_delayed(&self.state, self.address.sender(), msg, delay);
+            }
+
+            /// Register an interval future for actor(s).
+            ///
+            /// a `FutureHandler` would return that can be used to cancel it.
+            pub async fn run_interval<F>(&self, dur: Duration, f: F) -> FutureHandler<A>
+            where
+                F: FnMut(&mut A) -> Pin<Box<dyn Future<Output = ()> $( + $send)* + '_>> + Send + 'static,
+            {
+                let object = crate::object::FutureObject(f, PhantomData, std::sync::atomic::AtomicPtr::default()).pack();
+
+                register_interval(&self.state, self.address.sender(), object, dur).await
+            }
+
+            /// Spawn a future that would be canceled when the actor instance stops or restarts.
+            pub fn spawn<F>(&mut self, f: F)
+            where
+                F: Future<Output = ()> $( + $send)* + 'static,
+            {
+                self.children.retain(|child| !child.is_finished());
+                self.children.push(spawn_cancelable(f, |_| async {}));
+            }
+        }
+    };
+}
+
+#[cfg(not(any(feature = "actix-runtime", feature = "actix-runtime-mpsc")))]
+context_run!(Send);
+
+#[cfg(any(feature = "actix-runtime", feature = "actix-runtime-mpsc"))]
+context_run!();
+
+// push the delayed message to mailbox after the delay.
+fn schedule_delayed<A>(
+    state: &ActorState<A>,
+    tx: &WeakSender<ContextMessage<A>>,
+    msg: ContextMessage<A>,
+    dur: Duration,
+) where
+    A: Actor + 'static,
+{
+    if let Some(tx) = tx.upgrade() {
+        let handle_delay_on_shutdown = state.handle_delay_on_shutdown();
+        let state_clone = state.clone();
+        state.inc_delayed();
+
+        let handler = spawn_cancelable(runtime::delay_for(dur), move |either| async move {
+            let cancel = matches!(either, futures_util::future::Either::Left(_));
+            if !cancel || handle_delay_on_shutdown {
+                if let Err(msg) = tx.send_back(msg).await {
+                    state_clone.dead_letter(msg, DeadLetterReason::Closed);
+                }
+            } else {
+                state_clone.dead_letter(msg, DeadLetterReason::DelayCanceled);
+            }
+            state_clone.dec_delayed();
+        });
+
+        state.push_handler(vec![handler]);
+    } else {
+        state.dead_letter(msg, DeadLetterReason::Closed);
+    }
+}
+
+// register an interval future and spawn the loop pushing it to mailbox.
+async fn register_interval<A>(
+    state: &ActorState<A>,
+    tx: &WeakSender<ContextMessage<A>>,
+    interval_future: FutureObjectContainer<A>,
+    dur: Duration,
+) -> FutureHandler<A>
+where
+    A: Actor + 'static,
+{
+    // insert interval future to context and get it's index
+    let index = state.interval_futures.insert(interval_future).await;
+
+    // construct the interval future
+    let mut interval = runtime::interval(dur);
+    let ctx_tx = tx.clone();
+    let interval_loop = Box::pin(async move {
+        loop {
+            let _ = runtime::tick(&mut interval).await;
+            match ctx_tx.upgrade() {
+                Some(tx) => {
+                    let _ = tx
+                        .high_lane()
+                        .send(ContextMessage::Interval(IntervalMessage::Run(index)))
+                        .await;
+                }
+                None => break,
+            }
+            runtime::yield_now().await;
+        }
+    });
+
+    // spawn a cancelable future and use the handler to execute the cancellation.
+    let mut interval_handler = spawn_cancelable(interval_loop, |_| async {});
+
+    // we attach the index of interval future and a tx of our channel to handler.
+    interval_handler.attach_tx(index, tx.clone());
+
+    state.push_handler(vec![interval_handler.clone()]);
+
+    interval_handler
+}
+
+// receive the next message from mailbox and the receiver of actor instance.
+// high priority lanes are polled first and the rest are only polled when they are empty.
+async fn recv<A>(
+    rx_high: &mut Select<Recv<A>, Recv<A>>,
+    selector: &mut Select<Recv<A>, Recv<A>>,
+) -> Option<ContextMessage<A>>
+where
+    A: Actor,
+{
+    let high = rx_high.next();
+    let msg = selector.next();
+
+    match select(high, msg).await {
+        Either::Left((Some(msg), _)) => Some(msg),
+        // high priority lane is closed.
+        Either::Left((None, msg)) => msg.await,
+        Either::Right((msg, _)) => msg,
+    }
+}
+
+// check if the caller of a message has dropped the receiver of result.
+fn is_canceled<R>(tx: &Option<OneShotSender<R>>) -> bool {
+    matches!(tx, Some(tx) if tx.is_closed())
+}
+
+// reject a message left in mailbox when actor is stopped.
+// The caller would get a closed error or the message goes to the dead letter sink.
+fn reject<A>(state: &ActorState<A>, msg: ContextMessage<A>)
+where
+    A: Actor + 'static,
+{
+    if let Some(msg) = refuse(msg, ActixSendError::Closed) {
+        state.dead_letter(msg, DeadLetterReason::Closed);
+    }
+}
+
+// send the error to the caller of a message. The message is given back if there is no caller.
+fn refuse<A>(msg: ContextMessage<A>, e: ActixSendError) -> Option<ContextMessage<A>>
+where
+    A: Actor,
+{
+    match msg {
+        ContextMessage::Instant(InstantMessage::Static(Some(tx), _), _) => {
+            let _ = tx.send(Err(e));
+        }
+        ContextMessage::Instant(InstantMessage::Dynamic(Some(tx), _), _) => {
+            let _ = tx.send(Err(e));
+        }
+        ContextMessage::Instant(InstantMessage::Batch(tx, _), _) => {
+            let _ = tx.send(Err(e));
+        }
+        msg => return Some(msg),
+    }
+    None
+}
+
+// handle the messages dropped by MailboxPolicy when mailbox is full.
+// The caller would get a full error or the message goes to the dead letter sink.
+pub(crate) fn overflow<A>(
+    dead_letters: Option<Arc<dyn DeadLetterSink>>,
+) -> Overflow<ContextMessage<A>>
+where
+    A: Actor + 'static,
+{
+    Overflow {
+        reject: Box::new(move |msg| {
+            let msg = refuse(msg, ActixSendError::Full).and_then(ContextMessage::into_message);
+            if let (Some(sink), Some(msg)) = (dead_letters.as_ref(), msg) {
+                sink.dead_letter(DeadLetter::new(
+                    core::any::type_name::<A>(),
+                    DeadLetterReason::Full,
+                    AnyObjectContainer::pack(msg),
+                ));
+            }
+        }),
+    }
+}
+
+enum Event<A>
+where
+    A: Actor,
+{
+    Message(ContextMessage<A>),
+    Signal(Signal),
+}
+
+// The outcome of handling a message. Ordered from the best to the worst.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Outcome {
+    Ok,
+    // handle method returns an error.
+    Err,
+    Panic,
+}
+
+impl From<&Outcome> for HandleOutcome {
+    fn from(outcome: &Outcome) -> Self {
+        match outcome {
+            Outcome::Ok => HandleOutcome::Ok,
+            Outcome::Err => HandleOutcome::Err,
+            Outcome::Panic => HandleOutcome::Panicked,
+        }
+    }
+}
+
+impl Outcome {
+    fn from_result<R>(res: &Result<R, ActixSendError>, is_err: impl FnOnce(&R) -> bool) -> Self {
+        match res {
+            Ok(res) if is_err(res) => Outcome::Err,
+            Ok(_) => Outcome::Ok,
+            Err(_) => Outcome::Panic,
+        }
+    }
+}
+
+// catch the panic of a future running on actor.
+async fn catch_unwind<F>(fut: F) -> Result<F::Output, ActixSendError>
+where
+    F: Future,
+{
+    AssertUnwindSafe(fut)
+        .catch_unwind()
+        .await
+        .map_err(|_| ActixSendError::Panicked)
+}
+
+#[derive(Clone)]
 pub struct ActorContextState {
     id: usize,
     generation: usize,
 }
 
+impl ActorContextState {
+    /// The id of actor context.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The times actor instance restarted.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+}
+
 impl Debug for ActorContextState {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         f.debug_struct("ActorContextState")
@@ -238,11 +919,41 @@ where
     A: Actor,
 {
     ManualShutDown(OneShotSender<ActorContextState>),
-    Instant(InstantMessage<A>),
+    // the envelope is None for messages sent without one.
+    Instant(InstantMessage<A>, Option<Envelope>),
     Delayed(DelayedMessage<A>),
     Interval(IntervalMessage<A>),
 }
 
+impl<A> ContextMessage<A>
+where
+    A: Actor,
+{
+    // construct an instant message without envelope. The caller's span is carried in a new
+    // envelope when tracing is enabled.
+    pub(crate) fn instant(msg: InstantMessage<A>) -> Self {
+        #[cfg(feature = "tracing")]
+        return Self::instant_with(msg, Envelope::new());
+
+        #[cfg(not(feature = "tracing"))]
+        ContextMessage::Instant(msg, None)
+    }
+
+    pub(crate) fn instant_with(msg: InstantMessage<A>, mut envelope: Envelope) -> Self {
+        envelope.stamp();
+        ContextMessage::Instant(msg, Some(envelope))
+    }
+
+    // the message sent by user. None for boxed futures and control messages.
+    pub(crate) fn into_message(self) -> Option<A::Message> {
+        match self {
+            ContextMessage::Instant(InstantMessage::Static(_, msg), _) => Some(msg),
+            ContextMessage::Delayed(DelayedMessage::Static(msg, _)) => Some(msg),
+            _ => None,
+        }
+    }
+}
+
 // variants of interval future request
 pub(crate) enum IntervalMessage<A>
 where
@@ -262,11 +973,20 @@ pub(crate) enum InstantMessage<A>
 where
     A: Actor,
 {
-    Static(Option<OneShotSender<A::Result>>, A::Message),
+    Static(
+        Option<OneShotSender<Result<A::Result, ActixSendError>>>,
+        A::Message,
+    ),
     Dynamic(
-        Option<OneShotSender<AnyObjectContainer>>,
+        Option<OneShotSender<Result<AnyObjectContainer, ActixSendError>>>,
         FutureObjectContainer<A>,
     ),
+    // messages of Address::send_batch handled in sequence.
+    #[allow(clippy::type_complexity)]
+    Batch(
+        OneShotSender<Result<Vec<Result<A::Result, ActixSendError>>, ActixSendError>>,
+        Vec<A::Message>,
+    ),
 }
 
 // variants of delayed request
@@ -0,0 +1,146 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+
+use crate::actor::Actor;
+use crate::address::Address;
+use crate::object::AnyObjectContainer;
+
+/// The reason a message could not be delivered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// The mailbox of actor(s) is closed.
+    Closed,
+    /// The mailbox of actor(s) is full and the message is dropped following
+    /// `Builder::mailbox_policy`.
+    Full,
+    /// The caller has gone away before the message is handled.
+    Canceled,
+    /// The message can not be casted to the message type of a subscriber.
+    TypeCast,
+    /// The delay of message is canceled when actor(s) shutdown.
+    DelayCanceled,
+}
+
+/// A message could not be delivered to actor(s). See `Builder::dead_letters`.
+///
+/// *. The message is the message enum of actor(s) (or the message type of `Address::send_subscribe`
+/// for `DeadLetterReason::TypeCast`) and can be taken back with `DeadLetter::downcast`.
+///
+/// *. Boxed futures of `Address::run`(and alike) are not dead lettered.
+pub struct DeadLetter {
+    actor: &'static str,
+    reason: DeadLetterReason,
+    message: AnyObjectContainer,
+}
+
+impl DeadLetter {
+    pub(crate) fn new(
+        actor: &'static str,
+        reason: DeadLetterReason,
+        message: AnyObjectContainer,
+    ) -> Self {
+        Self {
+            actor,
+            reason,
+            message,
+        }
+    }
+
+    /// The type name of actor the message was sent to.
+    pub fn actor(&self) -> &'static str {
+        self.actor
+    }
+
+    /// The reason of dead letter.
+    pub fn reason(&self) -> DeadLetterReason {
+        self.reason
+    }
+
+    /// Take the message back. The dead letter is returned if the type is not matched.
+    pub fn downcast<M>(mut self) -> Result<M, Self>
+    where
+        M: 'static,
+    {
+        self.message.unpack::<M>().ok_or(self)
+    }
+}
+
+impl Debug for DeadLetter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("DeadLetter")
+            .field("actor", &self.actor)
+            .field("reason", &self.reason)
+            .finish()
+    }
+}
+
+/// The receiver of dead letters. Set with `Builder::dead_letters`.
+///
+/// *. Implemented for closures and `Address` of actor(s) handling `DeadLetter` as a message.
+///
+/// # Example:
+/// ```rust
+/// use actix_send::prelude::*;
+///
+/// #[actor]
+/// pub struct MyActor;
+///
+/// pub struct Ping;
+///
+/// #[handler_v2]
+/// impl MyActor {
+///     async fn handle(&mut self, _: Ping) {}
+/// }
+///
+/// #[actor]
+/// pub struct DeadLetterActor;
+///
+/// #[handler_v2]
+/// impl DeadLetterActor {
+///     async fn handle(&mut self, letter: DeadLetter) {
+///         println!("{:?}", letter);
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let dead_letters = DeadLetterActor::builder(|| async { DeadLetterActor })
+///         .start()
+///         .await;
+///
+///     let address = MyActor::builder(|| async { MyActor })
+///         .dead_letters(dead_letters)
+///         .start()
+///         .await;
+///
+///     let address2 = MyActor::builder(|| async { MyActor })
+///         .dead_letters(|letter: DeadLetter| println!("{:?}", letter))
+///         .start()
+///         .await;
+/// }
+/// ```
+pub trait DeadLetterSink: Send + Sync + 'static {
+    /// Called with every dead letter.
+    ///
+    /// *. The method is called on the hot path of actor(s) so it should return quickly.
+    fn dead_letter(&self, letter: DeadLetter);
+}
+
+impl<F> DeadLetterSink for F
+where
+    F: Fn(DeadLetter) + Send + Sync + 'static,
+{
+    fn dead_letter(&self, letter: DeadLetter) {
+        self(letter)
+    }
+}
+
+impl<A> DeadLetterSink for Address<A>
+where
+    A: Actor + 'static,
+    DeadLetter: Into<A::Message>,
+    Address<A>: Send + Sync,
+{
+    fn dead_letter(&self, letter: DeadLetter) {
+        self.do_send(letter);
+    }
+}
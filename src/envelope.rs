@@ -0,0 +1,169 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::util::span::Span;
+
+// The source of correlation ids.
+static NEXT_CORRELATION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The metadata of a message. Every message sent to actor(s) is wrapped in an envelope and it can
+/// be read with `Context::envelope` when handling the message.
+///
+/// Send a message with metadata by `Address::send_with`.
+///
+/// *. Clone the envelope of current message to propagate it's correlation id, deadline and
+/// headers to the messages sent by the handler.
+///
+/// # Example:
+/// ```rust
+/// use std::time::{Duration, Instant};
+///
+/// use actix_send::prelude::*;
+///
+/// #[actor]
+/// pub struct MyActor;
+///
+/// pub struct Ping;
+///
+/// #[handler_v2]
+/// impl MyActor {
+///     async fn handle(&mut self, _: Ping, ctx: &mut Context<Self>) -> u64 {
+///         assert_eq!(ctx.envelope().header("user"), Some("alice"));
+///         ctx.envelope().correlation_id()
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let address = MyActor::builder(|| async { MyActor }).start().await;
+///
+///     let envelope = Envelope::new()
+///         .with_correlation_id(42)
+///         .with_deadline(Instant::now() + Duration::from_secs(1))
+///         .with_header("user", "alice");
+///
+///     assert_eq!(address.send_with(envelope, Ping).await.unwrap(), 42);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Envelope {
+    correlation_id: u64,
+    created_at: Instant,
+    deadline: Option<Instant>,
+    sender: Option<String>,
+    headers: HashMap<String, String>,
+    // span of the caller. It's taken by actor before handling the message.
+    span: Span,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Envelope {
+    /// Construct an envelope with a new correlation id.
+    pub fn new() -> Self {
+        Self {
+            correlation_id: NEXT_CORRELATION_ID.fetch_add(1, Ordering::Relaxed),
+            created_at: Instant::now(),
+            deadline: None,
+            sender: None,
+            headers: HashMap::new(),
+            span: Span::none(),
+        }
+    }
+
+    /// Set the correlation id.
+    pub fn with_correlation_id(mut self, id: u64) -> Self {
+        self.correlation_id = id;
+        self
+    }
+
+    /// Set the deadline of the message.
+    ///
+    /// *. `Address::send_with` would return `ActixSendError::Timeout` when the deadline is reached
+    /// and the message is skipped by actor if it's not handled yet.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the identity of sender.
+    pub fn with_sender(mut self, sender: impl Into<String>) -> Self {
+        self.sender = Some(sender.into());
+        self
+    }
+
+    /// Add a user header.
+    pub fn with_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(key.into(), value.into());
+        self
+    }
+
+    /// The id for correlating the message with it's request and reply.
+    pub fn correlation_id(&self) -> u64 {
+        self.correlation_id
+    }
+
+    /// The time the message is created to be sent by `Address`.
+    ///
+    /// *. It's taken before waiting for `Builder::rate_limit` and the room of mailbox so the
+    /// waiting is included in the time before the message is handled.
+    ///
+    /// *. A message sent without an envelope gets one when `Context::envelope` is called and it's
+    /// the time of the call.
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+    }
+
+    /// The deadline of the message.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// The identity of sender.
+    pub fn sender(&self) -> Option<&str> {
+        self.sender.as_deref()
+    }
+
+    /// The value of user header with the given key.
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers.get(key).map(String::as_str)
+    }
+
+    /// All user headers.
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub(crate) fn is_expired(&self) -> bool {
+        matches!(self.deadline, Some(deadline) if Instant::now() >= deadline)
+    }
+
+    // stamp the envelope when the message is created by Address.
+    pub(crate) fn stamp(&mut self) {
+        self.created_at = Instant::now();
+        self.span = Span::current();
+    }
+
+    pub(crate) fn take_span(&mut self) -> Span {
+        core::mem::replace(&mut self.span, Span::none())
+    }
+}
+
+impl Debug for Envelope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Envelope")
+            .field("correlation_id", &self.correlation_id)
+            .field("created_at", &self.created_at)
+            .field("deadline", &self.deadline)
+            .field("sender", &self.sender)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
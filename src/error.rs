@@ -2,12 +2,19 @@ use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
 
 pub enum ActixSendError {
     Canceled,
+    Panicked,
     Closed,
+    Full,
     Timeout,
     Blocking,
     TypeCast,
     Subscribe,
     Broadcast,
+    UnknownInstance,
+    InstanceClosed,
+    RateLimited,
+    CircuitOpen,
+    Rejected(Box<dyn std::error::Error + Send + Sync>),
 }
 
 impl Debug for ActixSendError {
@@ -26,9 +33,15 @@ impl Debug for ActixSendError {
                 "description",
                 &"Oneshot channel is closed before we send anything through it",
             ),
+            ActixSendError::Panicked => fmt
+                .field("cause", &"Panicked")
+                .field("description", &"Actor panicked when handling the message"),
             ActixSendError::Closed => fmt
                 .field("cause", &"Closed")
                 .field("description", &"Actor's message channel is closed"),
+            ActixSendError::Full => fmt
+                .field("cause", &"Full")
+                .field("description", &"Actor's mailbox is full"),
             ActixSendError::Blocking => fmt
                 .field("cause", &"Blocking")
                 .field("description", &"Failed to run blocking code"),
@@ -38,6 +51,24 @@ impl Debug for ActixSendError {
             ActixSendError::Broadcast => fmt
                 .field("cause", &"Broadcast")
                 .field("description", &"This address can not broadcasting"),
+            ActixSendError::UnknownInstance => fmt.field("cause", &"UnknownInstance").field(
+                "description",
+                &"There is no actor instance with the given id",
+            ),
+            ActixSendError::InstanceClosed => fmt.field("cause", &"InstanceClosed").field(
+                "description",
+                &"The actor instance with the given id is stopped",
+            ),
+            ActixSendError::RateLimited => fmt
+                .field("cause", &"RateLimited")
+                .field("description", &"The rate limit of actor(s) is exceeded"),
+            ActixSendError::CircuitOpen => fmt
+                .field("cause", &"CircuitOpen")
+                .field("description", &"The circuit breaker of actor(s) is open"),
+            ActixSendError::Rejected(e) => fmt
+                .field("cause", &"Rejected")
+                .field("description", &"Middleware rejected the message")
+                .field("error", e),
         };
 
         fmt.finish()
@@ -46,7 +77,7 @@ impl Debug for ActixSendError {
 
 impl Display for ActixSendError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "({})", self)
+        write!(f, "({:?})", self)
     }
 }
 
@@ -30,7 +30,9 @@
 //!         8
 //!     }
 //!
-//!     async fn handle(&mut self, _:Message2) -> u16 {
+//!     // handle method can optionally take the Context of actor instance as argument.
+//!     async fn handle(&mut self, _:Message2, ctx: &mut Context<Self>) -> u16 {
+//!         let _id = ctx.id();
 //!         16
 //!     }
 //!
@@ -130,35 +132,84 @@
 //! | `async-std-runtime` | Enable support for the `async-std` crate. | [async-channel](https://crates.io/crates/async-channel)<br>[async-std](https://crates.io/crates/async-std)<br>[tokio](https://crates.io/crates/tokio) with `sync` feature | no |
 //! | `actix-runtime` | Enable support for the `actix-rt` crate. | [actix-rt](https://crates.io/crates/actix-rt)<br>[async-channel](https://crates.io/crates/async-channel)<br>[tokio](https://crates.io/crates/tokio) with `sync` feature | no |
 //! | `actix-runtime-mpsc` | Enable support for mpsc actor for `actix-rt`. actor runs on single thread with a thread safe sender for message. | [actix-rt](https://crates.io/crates/actix-rt)<br>[tokio](https://crates.io/crates/tokio) with `sync` feature | no |
+//! | `metrics` | Enable `MetricsSink` for exporting metrics of actors with `Builder::metrics_sink`. | | no |
+//! | `tracing` | Handle messages in a child span of the caller's span. The span is named `handle` with the actor, message and id of actor instance as fields. | [tracing](https://crates.io/crates/tracing) | no |
 
 #![forbid(unsafe_code)]
 #![deny(unused_variables)]
 
 pub(crate) mod actor;
 pub(crate) mod address;
+#[cfg(not(feature = "actix-runtime-mpsc"))]
+pub(crate) mod autoscale;
 pub(crate) mod builder;
+pub(crate) mod circuit_breaker;
 pub(crate) mod context;
+pub(crate) mod dead_letter;
+pub(crate) mod envelope;
 pub(crate) mod error;
 pub(crate) mod interval;
+pub(crate) mod metrics;
+pub(crate) mod middleware;
 pub(crate) mod object;
+pub(crate) mod rate_limit;
 pub(crate) mod receiver;
+pub(crate) mod retry;
+pub(crate) mod router;
 pub(crate) mod sender;
 pub(crate) mod stream;
 pub(crate) mod subscribe;
+pub(crate) mod supervisor;
 pub(crate) mod util;
 
 pub mod prelude {
-    pub use crate::actor::{Actor, Handler};
-    pub use crate::address::{Address, MapResult, WeakAddress};
-    pub use crate::builder::Builder;
+    pub use crate::actor::{Actor, FallibleResult, Handler};
+    pub use crate::address::{
+        Address, MapResult, Priority, ReplyFuture, SendHandle, StopMode, WeakAddress,
+    };
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub use crate::autoscale::{Autoscale, ScaleEvent, ScaleReason};
+    pub use crate::builder::{Builder, MailboxPolicy};
+    pub use crate::circuit_breaker::{CircuitBreaker, CircuitState};
+    pub use crate::context::Context;
+    pub use crate::dead_letter::{DeadLetter, DeadLetterReason, DeadLetterSink};
+    pub use crate::envelope::Envelope;
     pub use crate::error::ActixSendError;
+    #[cfg(feature = "metrics")]
+    pub use crate::metrics::MetricsSink;
+    pub use crate::metrics::{Histogram, InstanceMetrics, Metrics};
+    pub use crate::middleware::{HandleOutcome, MessageInfo, Middleware};
+    pub use crate::rate_limit::RateLimit;
+    pub use crate::retry::{Backoff, Idempotent, RetryPolicy};
+    pub use crate::router::{RouteKey, Router};
     pub use crate::stream::{ActorSkipStream, ActorStream};
+    pub use crate::supervisor::{Child, Supervisor, SupervisorHandle, SupervisorStrategy};
     pub use crate::util::runtime::spawn_blocking as actix_send_blocking;
     pub use actix_send_macros::*;
     pub use async_trait::async_trait;
 }
 
-pub use crate::builder::Builder;
+pub use crate::address::{Priority, ReplyFuture, SendHandle, StopMode};
+#[cfg(not(feature = "actix-runtime-mpsc"))]
+pub use crate::autoscale::{Autoscale, ScaleEvent, ScaleReason};
+pub use crate::builder::{Builder, MailboxPolicy};
+pub use crate::circuit_breaker::{CircuitBreaker, CircuitState};
+pub use crate::context::{ActorContextState, Context};
+pub use crate::dead_letter::{DeadLetter, DeadLetterReason, DeadLetterSink};
+pub use crate::envelope::Envelope;
+#[cfg(feature = "metrics")]
+pub use crate::metrics::MetricsSink;
+pub use crate::metrics::{Histogram, InstanceMetrics, Metrics};
+pub use crate::middleware::{HandleOutcome, MessageInfo, Middleware};
+pub use crate::rate_limit::RateLimit;
+pub use crate::retry::{Backoff, Idempotent, RetryPolicy};
+pub use crate::router::{RouteKey, Router};
+
+#[doc(hidden)]
+pub use crate::retry::copy_idempotent;
+#[doc(hidden)]
+pub use crate::router::hash_route_key;
+pub use crate::supervisor::{Child, Supervisor, SupervisorHandle, SupervisorStrategy};
 
 #[cfg(all(feature = "tokio-runtime", feature = "async-std-runtime"))]
 compile_error!("Only one runtime can be enabled");
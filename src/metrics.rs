@@ -0,0 +1,359 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use std::collections::HashMap;
+
+use crate::builder::Config;
+use crate::context::ActorContextState;
+use crate::util::smart_pointer::{Lock, RefCounter};
+
+// The upper bounds of histogram buckets. Latencies larger than the last bound go to an extra
+// bucket.
+const BOUNDS: [Duration; 16] = [
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// Export the metrics of actor(s) to a monitoring system.
+///
+/// All methods are called on the hot path of actor(s) so they should return quickly.
+///
+/// *. Queue depth is a gauge and can be polled with `Address::metrics`.
+#[cfg(feature = "metrics")]
+pub trait MetricsSink: Send + Sync + 'static {
+    /// Called after an actor instance handled a message.
+    fn handled(&self, actor: &'static str, message: &'static str, id: usize, latency: Duration);
+
+    /// Called after an actor instance restarted.
+    #[allow(unused_variables)]
+    fn restarted(&self, actor: &'static str, id: usize, generation: usize) {}
+
+    /// Called when sending to actor(s) timed out.
+    #[allow(unused_variables)]
+    fn timed_out(&self, actor: &'static str) {}
+
+    /// Called when a message is skipped because it's caller has gone away.
+    #[allow(unused_variables)]
+    fn canceled(&self, actor: &'static str) {}
+}
+
+// A latency histogram can be recorded by multiple actor instances.
+#[derive(Default)]
+pub(crate) struct AtomicHistogram {
+    buckets: [AtomicU64; BOUNDS.len() + 1],
+    sum: AtomicU64,
+}
+
+impl AtomicHistogram {
+    fn record(&self, dur: Duration) {
+        let idx = BOUNDS
+            .iter()
+            .position(|bound| dur <= *bound)
+            .unwrap_or(BOUNDS.len());
+
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(dur.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Histogram {
+        Histogram {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|b| b.load(Ordering::Relaxed))
+                .collect(),
+            sum: Duration::from_nanos(self.sum.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+// Metrics shared by actor instances of one address.
+pub(crate) struct MetricsState {
+    // latencies are only recorded with Builder::metrics or a sink.
+    enabled: bool,
+    timeouts: AtomicUsize,
+    // latencies of actor instances by id.
+    instances: Lock<HashMap<usize, RefCounter<AtomicHistogram>>>,
+    // latencies of messages by name.
+    messages: Lock<HashMap<&'static str, RefCounter<AtomicHistogram>>>,
+    // the sink and the type name of actor.
+    #[cfg(feature = "metrics")]
+    sink: Option<(RefCounter<dyn MetricsSink>, &'static str)>,
+}
+
+impl MetricsState {
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn new(config: &Config, actor: &'static str) -> Self {
+        #[cfg(feature = "metrics")]
+        let enabled = config.metrics || config.metrics_sink.is_some();
+        #[cfg(not(feature = "metrics"))]
+        let enabled = config.metrics;
+
+        Self {
+            enabled,
+            timeouts: AtomicUsize::new(0),
+            instances: Lock::new(HashMap::new()),
+            messages: Lock::new(HashMap::new()),
+            #[cfg(feature = "metrics")]
+            sink: config.metrics_sink.clone().map(|sink| (sink, actor)),
+        }
+    }
+
+    // the recorder of an actor instance.
+    pub(crate) fn recorder(&self, id: usize) -> Recorder {
+        let latency = self.instances.lock().entry(id).or_default().clone();
+
+        Recorder {
+            latency,
+            messages: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) fn remove_instance(&self, id: usize) {
+        self.instances.lock().remove(&id);
+    }
+
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn restarted(&self, id: usize, generation: usize) {
+        #[cfg(feature = "metrics")]
+        if let Some((sink, actor)) = self.sink.as_ref() {
+            sink.restarted(actor, id, generation);
+        }
+    }
+
+    pub(crate) fn timed_out(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "metrics")]
+        if let Some((sink, actor)) = self.sink.as_ref() {
+            sink.timed_out(actor);
+        }
+    }
+
+    pub(crate) fn canceled(&self) {
+        #[cfg(feature = "metrics")]
+        if let Some((sink, actor)) = self.sink.as_ref() {
+            sink.canceled(actor);
+        }
+    }
+
+    pub(crate) fn snapshot(
+        &self,
+        queue_len: usize,
+        canceled: usize,
+        states: Vec<ActorContextState>,
+    ) -> Metrics {
+        let instances = {
+            let latencies = self.instances.lock();
+            states
+                .into_iter()
+                .map(|state| InstanceMetrics {
+                    latency: latencies
+                        .get(&state.id())
+                        .map(|h| h.snapshot())
+                        .unwrap_or_default(),
+                    state,
+                })
+                .collect()
+        };
+
+        let mut messages = self
+            .messages
+            .lock()
+            .iter()
+            .map(|(name, h)| (*name, h.snapshot()))
+            .collect::<Vec<_>>();
+        messages.sort_by_key(|(name, _)| *name);
+
+        Metrics {
+            queue_len,
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            canceled,
+            instances,
+            messages,
+        }
+    }
+}
+
+// Record the latencies of an actor instance.
+// Histograms are cached so recording would not lock the shared state every time.
+pub(crate) struct Recorder {
+    latency: RefCounter<AtomicHistogram>,
+    messages: HashMap<&'static str, RefCounter<AtomicHistogram>>,
+}
+
+impl Recorder {
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn record(
+        &mut self,
+        state: &MetricsState,
+        id: usize,
+        message: &'static str,
+        dur: Duration,
+    ) {
+        self.latency.record(dur);
+
+        self.messages
+            .entry(message)
+            .or_insert_with(|| state.messages.lock().entry(message).or_default().clone())
+            .record(dur);
+
+        #[cfg(feature = "metrics")]
+        if let Some((sink, actor)) = state.sink.as_ref() {
+            sink.handled(actor, message, id, dur);
+        }
+    }
+}
+
+/// A snapshot of the metrics of actor(s). See `Address::metrics`.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    queue_len: usize,
+    timeouts: usize,
+    canceled: usize,
+    instances: Vec<InstanceMetrics>,
+    messages: Vec<(&'static str, Histogram)>,
+}
+
+impl Metrics {
+    /// The number of messages waiting in mailbox.
+    pub fn queue_len(&self) -> usize {
+        self.queue_len
+    }
+
+    /// The number of timeouts returned by `Address::send`(and alike).
+    pub fn timeouts(&self) -> usize {
+        self.timeouts
+    }
+
+    /// The number of messages skipped because their callers have gone away.
+    pub fn canceled(&self) -> usize {
+        self.canceled
+    }
+
+    /// The metrics of running actor instances sorted by id.
+    pub fn instances(&self) -> &[InstanceMetrics] {
+        &self.instances
+    }
+
+    /// The latencies of handling messages by message name sorted by name.
+    ///
+    /// *. The name is from `Handler::message_name`. `Address::run`(and alike) are named as `run`.
+    pub fn messages(&self) -> &[(&'static str, Histogram)] {
+        &self.messages
+    }
+
+    /// The latencies of handling the message with the given name.
+    pub fn message(&self, name: &str) -> Option<&Histogram> {
+        self.messages
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, h)| h)
+    }
+}
+
+/// The metrics of an actor instance.
+#[derive(Clone, Debug)]
+pub struct InstanceMetrics {
+    state: ActorContextState,
+    latency: Histogram,
+}
+
+impl InstanceMetrics {
+    /// The id of actor instance.
+    pub fn id(&self) -> usize {
+        self.state.id()
+    }
+
+    /// The times actor instance restarted.
+    pub fn generation(&self) -> usize {
+        self.state.generation()
+    }
+
+    /// The number of messages handled by actor instance.
+    pub fn handled(&self) -> u64 {
+        self.latency.count()
+    }
+
+    /// The latencies of handling messages by actor instance.
+    pub fn latency(&self) -> &Histogram {
+        &self.latency
+    }
+}
+
+/// A histogram of latencies of handling messages.
+#[derive(Clone)]
+pub struct Histogram {
+    buckets: Vec<u64>,
+    sum: Duration,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; BOUNDS.len() + 1],
+            sum: Duration::from_secs(0),
+        }
+    }
+}
+
+impl Histogram {
+    /// The number of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// The sum of recorded latencies.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// The average of recorded latencies.
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::from_secs(0),
+            count => Duration::from_nanos((self.sum.as_nanos() / count as u128) as u64),
+        }
+    }
+
+    /// The number of latencies in every bucket paired with the upper bound(inclusive) of bucket.
+    ///
+    /// *. The last bucket has no upper bound.
+    pub fn buckets(&self) -> Vec<(Option<Duration>, u64)> {
+        BOUNDS
+            .iter()
+            .map(|bound| Some(*bound))
+            .chain(Some(None))
+            .zip(self.buckets.iter().copied())
+            .collect()
+    }
+}
+
+impl Debug for Histogram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Histogram")
+            .field("count", &self.count())
+            .field("sum", &self.sum)
+            .field("mean", &self.mean())
+            .finish()
+    }
+}
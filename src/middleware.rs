@@ -0,0 +1,180 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::time::Duration;
+
+use std::sync::Arc;
+
+use crate::envelope::Envelope;
+use crate::error::ActixSendError;
+
+/// Intercept the messages handled by actor(s). Register with `Builder::wrap`.
+///
+/// `Middleware::before` is called in the order of registering and `Middleware::after` is called
+/// in the reverse order.
+///
+/// *. Messages sent by `Address::run`(and alike) are intercepted with the message name `run`.
+///
+/// *. Interval futures are not intercepted.
+///
+/// # Example:
+/// ```rust
+/// use std::time::Duration;
+///
+/// use actix_send::prelude::*;
+///
+/// #[actor]
+/// pub struct MyActor;
+///
+/// pub struct Ping;
+///
+/// #[handler_v2]
+/// impl MyActor {
+///     async fn handle(&mut self, _: Ping) -> u8 {
+///         8
+///     }
+/// }
+///
+/// struct Auth;
+///
+/// impl Middleware for Auth {
+///     fn before(&self, info: &MessageInfo<'_>) -> Result<(), ActixSendError> {
+///         match info.envelope().header("user") {
+///             Some(_) => Ok(()),
+///             None => Err(ActixSendError::Rejected("unknown user".into())),
+///         }
+///     }
+///
+///     fn after(&self, info: &MessageInfo<'_>, outcome: HandleOutcome, elapsed: Duration) {
+///         println!("{} handled {}: {:?} in {:?}", info.actor(), info.message(), outcome, elapsed);
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let address = MyActor::builder(|| async { MyActor })
+///         .wrap(Auth)
+///         .start()
+///         .await;
+///
+///     assert!(address.send(Ping).await.is_err());
+///
+///     let envelope = Envelope::new().with_header("user", "alice");
+///     assert_eq!(address.send_with(envelope, Ping).await.unwrap(), 8);
+/// }
+/// ```
+pub trait Middleware: Send + Sync + 'static {
+    /// Called before handling a message.
+    ///
+    /// Return an error to reject the message. The error is returned to the caller and the handler
+    /// and the middlewares registered after this one would not be called.
+    #[allow(unused_variables)]
+    fn before(&self, info: &MessageInfo<'_>) -> Result<(), ActixSendError> {
+        Ok(())
+    }
+
+    /// Called after handling a message with the time it took.
+    ///
+    /// *. When a message is rejected only the middlewares passed it would be called with
+    /// `HandleOutcome::Rejected`.
+    #[allow(unused_variables)]
+    fn after(&self, info: &MessageInfo<'_>, outcome: HandleOutcome, elapsed: Duration) {}
+}
+
+/// The message being handled by an actor instance.
+pub struct MessageInfo<'a> {
+    actor: &'static str,
+    message: &'static str,
+    id: usize,
+    envelope: &'a Envelope,
+}
+
+impl<'a> MessageInfo<'a> {
+    pub(crate) fn new(
+        actor: &'static str,
+        message: &'static str,
+        id: usize,
+        envelope: &'a Envelope,
+    ) -> Self {
+        Self {
+            actor,
+            message,
+            id,
+            envelope,
+        }
+    }
+
+    /// The type name of actor.
+    pub fn actor(&self) -> &'static str {
+        self.actor
+    }
+
+    /// The name of message from `Handler::message_name`.
+    pub fn message(&self) -> &'static str {
+        self.message
+    }
+
+    /// The id of actor instance.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The envelope of message.
+    pub fn envelope(&self) -> &Envelope {
+        self.envelope
+    }
+}
+
+impl Debug for MessageInfo<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("MessageInfo")
+            .field("actor", &self.actor)
+            .field("message", &self.message)
+            .field("id", &self.id)
+            .field("envelope", &self.envelope)
+            .finish()
+    }
+}
+
+/// The outcome of handling a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandleOutcome {
+    /// The message is handled.
+    Ok,
+    /// The handle method of a fallible message returned an error.
+    Err,
+    /// The actor panicked when handling the message.
+    Panicked,
+    /// A middleware rejected the message.
+    Rejected,
+}
+
+// call the before hooks in order. The passed middlewares are notified if the message is rejected.
+pub(crate) fn before(
+    middlewares: &[Arc<dyn Middleware>],
+    info: &MessageInfo<'_>,
+) -> Result<(), ActixSendError> {
+    for (i, middleware) in middlewares.iter().enumerate() {
+        if let Err(e) = middleware.before(info) {
+            after(
+                &middlewares[..i],
+                info,
+                HandleOutcome::Rejected,
+                Duration::from_secs(0),
+            );
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+// call the after hooks in reverse order.
+pub(crate) fn after(
+    middlewares: &[Arc<dyn Middleware>],
+    info: &MessageInfo<'_>,
+    outcome: HandleOutcome,
+    elapsed: Duration,
+) {
+    for middleware in middlewares.iter().rev() {
+        middleware.after(info, outcome, elapsed);
+    }
+}
@@ -47,6 +47,7 @@ macro_rules! object {
         }
 
         // The type we want to implement FutureTrait.
+        #[allow(dead_code)]
         pub(crate) struct FutureObject<A, F, R>(
             pub(crate) F,
             pub(crate) PhantomData<R>,
@@ -0,0 +1,125 @@
+use core::time::Duration;
+
+use std::time::Instant;
+
+use crate::util::smart_pointer::Lock;
+
+/// Limit the rate of messages sent to actor(s) with a token bucket. Set with
+/// `Builder::rate_limit`.
+///
+/// Tokens are refilled evenly over the period and every message sent through `Address` takes
+/// one. When the bucket is empty `Address::send`(and alike) would wait for the next token and
+/// `Address::try_send` would return `ActixSendError::RateLimited`.
+///
+/// # Example:
+/// ```rust
+/// use std::time::Duration;
+///
+/// use actix_send::prelude::*;
+///
+/// #[actor(no_static)]
+/// pub struct MyActor;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     // 100 messages per second for every actor instance with bursts of at most 10 messages.
+///     let limit = RateLimit::per_second(100).burst(10).per_instance();
+///
+///     let address: Address<MyActor> = MyActor::builder(|| async { MyActor })
+///         .num(2)
+///         .rate_limit(limit)
+///         .start()
+///         .await;
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    num: u32,
+    per: Duration,
+    burst: u32,
+    per_instance: bool,
+}
+
+impl RateLimit {
+    /// Allow num messages in the given period.
+    pub fn new(num: u32, per: Duration) -> Self {
+        assert!(num > 0, "The number of messages must be larger than 0");
+        assert!(
+            per > Duration::from_secs(0),
+            "The period of rate limit must be larger than 0"
+        );
+
+        Self {
+            num,
+            per,
+            burst: num,
+            per_instance: false,
+        }
+    }
+
+    /// Allow num messages per second.
+    pub fn per_second(num: u32) -> Self {
+        Self::new(num, Duration::from_secs(1))
+    }
+
+    /// Set the capacity of token bucket which is the max number of messages can be sent at once.
+    ///
+    /// Default is the same as the number of messages in one period.
+    pub fn burst(mut self, burst: u32) -> Self {
+        assert!(burst > 0, "The burst of rate limit must be larger than 0");
+        self.burst = burst;
+        self
+    }
+
+    /// Apply the rate to every running actor instance instead of the whole address.
+    ///
+    /// *. The rate of address would grow and shrink with `Address::scale_to` and
+    /// `Builder::autoscale`.
+    pub fn per_instance(mut self) -> Self {
+        self.per_instance = true;
+        self
+    }
+}
+
+// A token bucket implemented as generic cell rate algorithm.
+// The bucket is represented by the theoretical arrival time of the next message.
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    tat: Lock<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tat: Lock::new(Instant::now()),
+        }
+    }
+
+    // reserve a token. Return the time to wait for it or None if the wait is longer than max.
+    pub(crate) fn reserve(&self, instances: usize, max: Duration) -> Option<Duration> {
+        let num = match self.limit.per_instance {
+            true => self.limit.num.saturating_mul(instances.max(1) as u32),
+            false => self.limit.num,
+        };
+        let interval = self.limit.per / num;
+        let tolerance = interval * (self.limit.burst - 1);
+
+        let now = Instant::now();
+        let mut tat = self.tat.lock();
+        let next = (*tat).max(now);
+
+        let wait = next
+            .checked_sub(tolerance)
+            .map(|at| at.saturating_duration_since(now))
+            .unwrap_or_else(|| Duration::from_secs(0));
+
+        if wait > max {
+            return None;
+        }
+
+        *tat = next + interval;
+
+        Some(wait)
+    }
+}
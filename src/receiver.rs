@@ -3,7 +3,6 @@ use core::task::{Context, Poll};
 
 use futures_util::stream::Stream;
 
-use crate::error::ActixSendError;
 use crate::util::channel::Receiver as AsyncChannelReceiver;
 
 // A wrapper for crate::util::channel::Receiver so we have a unified abstraction for different
@@ -22,8 +21,9 @@ pub mod recv {
     }
 
     impl<M> Receiver<M> {
-        pub(crate) async fn recv(&self) -> Result<M, ActixSendError> {
-            self.inner.recv().await.map_err(|_| ActixSendError::Closed)
+        // close the channel. Messages already in channel can still be received.
+        pub(crate) fn close(&mut self) {
+            self.inner.close();
         }
     }
 
@@ -41,8 +41,9 @@ pub mod recv {
     use super::*;
 
     impl<M> Receiver<M> {
-        pub(crate) async fn recv(&mut self) -> Result<M, ActixSendError> {
-            self.inner.recv().await.ok_or(ActixSendError::Closed)
+        // close the channel. Messages already in channel can still be received.
+        pub(crate) fn close(&mut self) {
+            self.inner.close();
         }
     }
 
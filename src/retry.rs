@@ -0,0 +1,210 @@
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::ActixSendError;
+
+/// A message can be sent more than once without changing the outcome.
+///
+/// Only idempotent messages are retried so a message would never be resent silently.
+///
+/// *. `Address::send_retry` accepts any message implementing it.
+///
+/// *. `#[actor_mod]` and `#[handler_v2]` would use it for messages marked as idempotent so
+/// `Builder::retry` applies to them.
+pub trait Idempotent: Clone {}
+
+// copy an idempotent message. It's used by the macros to implement MapResult::retry_copy.
+pub fn copy_idempotent<M: Idempotent>(msg: &M) -> M {
+    msg.clone()
+}
+
+/// The wait between two attempts of `RetryPolicy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backoff {
+    /// Wait the same duration before every retry.
+    Fixed(Duration),
+    /// Wait from min and double the wait on every retry until it reaches max.
+    Exponential(Duration, Duration),
+}
+
+/// Retry sending a message when it fails with a transient error.
+///
+/// Set for all idempotent messages with `Builder::retry` or for one call with
+/// `Address::send_retry`.
+///
+/// # Example:
+/// ```rust
+/// use std::time::Duration;
+///
+/// use actix_send::prelude::*;
+///
+/// #[actor]
+/// pub struct MyActor;
+///
+/// #[derive(Clone)]
+/// pub struct Get;
+///
+/// impl Idempotent for Get {}
+///
+/// #[handler_v2]
+/// impl MyActor {
+///     #[idempotent]
+///     async fn handle(&mut self, _: Get) -> u8 {
+///         8
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let policy = RetryPolicy::new(3)
+///         .backoff(Backoff::Exponential(
+///             Duration::from_millis(10),
+///             Duration::from_secs(1),
+///         ))
+///         .jitter();
+///
+///     let address = MyActor::builder(|| async { MyActor })
+///         .retry(policy.clone())
+///         .start()
+///         .await;
+///
+///     assert_eq!(address.send(Get).await.unwrap(), 8);
+///     assert_eq!(address.send_retry(Get, &policy).await.unwrap(), 8);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    backoff: Backoff,
+    jitter: bool,
+    retry_on: Arc<dyn Fn(&ActixSendError) -> bool + Send + Sync>,
+    retry_handler_err: bool,
+    // the state of xorshift generator for jitter. Clones of the policy share it.
+    rng: Arc<AtomicU64>,
+}
+
+impl RetryPolicy {
+    /// Send a message at most the given times including the first attempt.
+    pub fn new(max_attempts: usize) -> Self {
+        assert!(
+            max_attempts > 0,
+            "The number of attempts must be larger than 0"
+        );
+
+        Self {
+            max_attempts,
+            backoff: Backoff::Fixed(Duration::from_millis(100)),
+            jitter: false,
+            retry_on: Arc::new(|e| matches!(e, ActixSendError::Closed | ActixSendError::Timeout)),
+            retry_handler_err: false,
+            rng: Arc::new(AtomicU64::new(seed())),
+        }
+    }
+
+    /// Set the wait between two attempts.
+    ///
+    /// Default is `Backoff::Fixed` of 100 milliseconds.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Randomize every wait to somewhere between the half of it and the whole of it so callers
+    /// failed at the same time would not retry at the same time.
+    ///
+    /// Default is false.
+    pub fn jitter(mut self) -> Self {
+        self.jitter = true;
+        self
+    }
+
+    /// Set the errors should be retried.
+    ///
+    /// Default is `ActixSendError::Closed` and `ActixSendError::Timeout`.
+    pub fn retry_on<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&ActixSendError) -> bool + Send + Sync + 'static,
+    {
+        self.retry_on = Arc::new(f);
+        self
+    }
+
+    /// Retry when the handle method of a fallible message returns an error.
+    ///
+    /// Default is false.
+    pub fn retry_handler_err(mut self) -> Self {
+        self.retry_handler_err = true;
+        self
+    }
+
+    pub(crate) fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    pub(crate) fn should_retry(&self, e: &ActixSendError) -> bool {
+        (self.retry_on)(e)
+    }
+
+    pub(crate) fn should_retry_handler_err(&self) -> bool {
+        self.retry_handler_err
+    }
+
+    // the wait after the given attempt failed. attempt starts from 1.
+    pub(crate) fn wait(&self, attempt: usize) -> Duration {
+        let wait = match self.backoff {
+            Backoff::Fixed(dur) => dur,
+            Backoff::Exponential(min, max) => {
+                let shift = (attempt - 1).min(31) as u32;
+                min.checked_mul(1 << shift).unwrap_or(max).min(max)
+            }
+        };
+
+        match self.jitter {
+            true => {
+                let half = wait / 2;
+                let nanos = (wait - half).as_nanos() as u64;
+                half + Duration::from_nanos(self.next_random() % nanos.max(1))
+            }
+            false => wait,
+        }
+    }
+
+    // advance the xorshift64 generator and return the next number.
+    fn next_random(&self) -> u64 {
+        let mut next = 0;
+        let _ = self
+            .rng
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |mut x| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                next = x;
+                Some(x)
+            });
+        next
+    }
+}
+
+// seed the xorshift generator with the current time. The state of xorshift must not be zero.
+fn seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|dur| dur.as_nanos() as u64)
+        .unwrap_or(0)
+        | 1
+}
+
+impl Debug for RetryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("backoff", &self.backoff)
+            .field("jitter", &self.jitter)
+            .field("retry_handler_err", &self.retry_handler_err)
+            .finish()
+    }
+}
@@ -0,0 +1,71 @@
+use core::hash::{Hash, Hasher};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use std::collections::hash_map::DefaultHasher;
+
+use crate::actor::Actor;
+use crate::context::ContextMessage;
+use crate::sender::Sender;
+
+/// The way of dispatching messages to actor instances of one address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Router {
+    /// All actor instances steal work from one shared mailbox.
+    WorkStealing,
+    /// Messages are dispatched to actor instances in turn.
+    RoundRobin,
+    /// Messages are dispatched to the actor instance with the fewest messages in it's mailbox.
+    LeastLoaded,
+    /// Messages are dispatched by the key from `RouteKey`. Messages with the same key would always
+    /// go to the same actor instance as long as it's alive.
+    ///
+    /// *. Messages without a key would be dispatched like `Router::RoundRobin`.
+    ConsistentHash,
+}
+
+/// The key of a message for `Router::ConsistentHash`.
+///
+/// *. `#[actor_mod]` and `#[handler_v2]` would use it for messages marked as route_key.
+pub trait RouteKey {
+    type Key: Hash;
+
+    fn route_key(&self) -> Self::Key;
+}
+
+// hash the key of message. It's used by the macros to implement MapResult::route_key.
+pub fn hash_route_key<K: Hash>(key: K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+// pick the mailbox of an actor instance for a message.
+// return None if all actor instances are closed.
+pub(crate) fn route<'a, A>(
+    router: Router,
+    senders: &'a [(usize, Sender<ContextMessage<A>>)],
+    key: Option<u64>,
+    next: &AtomicUsize,
+) -> Option<&'a Sender<ContextMessage<A>>>
+where
+    A: Actor + 'static,
+{
+    let alive = senders.iter().filter(|(_, tx)| !tx.is_closed());
+
+    match (router, key) {
+        (Router::WorkStealing, _) => None,
+        // rendezvous hashing. The instance with the highest score of the key wins so only the
+        // keys of a closed or newly added instance are moved.
+        (Router::ConsistentHash, Some(key)) => alive
+            .max_by_key(|(id, _)| hash_route_key((key, *id)))
+            .map(|(_, tx)| tx),
+        (Router::LeastLoaded, _) => alive.min_by_key(|(_, tx)| tx.len()).map(|(_, tx)| tx),
+        (Router::RoundRobin, _) | (Router::ConsistentHash, None) => {
+            let len = senders.len();
+            let start = next.fetch_add(1, Ordering::Relaxed);
+            (0..len)
+                .map(|i| &senders[(start + i) % len].1)
+                .find(|tx| !tx.is_closed())
+        }
+    }
+}
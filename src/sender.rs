@@ -1,11 +1,14 @@
+use core::sync::atomic::AtomicUsize;
 use core::time::Duration;
 
 use crate::actor::Actor;
+use crate::builder::MailboxPolicy;
 use crate::context::ContextMessage;
 use crate::error::ActixSendError;
+use crate::router::{route, Router};
 use crate::util::{
-    channel::Sender as AsyncChannelSender,
-    smart_pointer::{RefCounter, WeakRefCounter},
+    channel::{Receiver as AsyncChannelReceiver, Sender as AsyncChannelSender, TrySendError},
+    smart_pointer::{Lock, RefCounter, WeakRefCounter},
 };
 
 // A wrapper for crate::util::channel::Sender so we have a unified abstraction for different
@@ -13,12 +16,28 @@ use crate::util::{
 
 pub struct Sender<M> {
     inner: RefCounter<AsyncChannelSender<M>>,
+    // A lane for high priority and control messages. Actor would receive from it first.
+    high: Option<RefCounter<Sender<M>>>,
+    policy: MailboxPolicy,
+    // A receiver kept for MailboxPolicy::DropOldest so we can evict the oldest message.
+    evict: Option<RefCounter<AsyncChannelReceiver<M>>>,
+    overflow: Option<RefCounter<Overflow<M>>>,
+}
+
+// Handle the messages dropped by MailboxPolicy when mailbox is full.
+pub(crate) struct Overflow<M> {
+    // reject a dropped message to it's caller or the dead letter sink.
+    pub(crate) reject: Box<dyn Fn(M) + Send + Sync>,
 }
 
 impl<M> Clone for Sender<M> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            high: self.high.clone(),
+            policy: self.policy,
+            evict: self.evict.clone(),
+            overflow: self.overflow.clone(),
         }
     }
 }
@@ -27,6 +46,10 @@ impl<M> From<AsyncChannelSender<M>> for Sender<M> {
     fn from(sender: AsyncChannelSender<M>) -> Self {
         Self {
             inner: RefCounter::new(sender),
+            high: None,
+            policy: MailboxPolicy::Block,
+            evict: None,
+            overflow: None,
         }
     }
 }
@@ -38,73 +61,239 @@ where
     pub(crate) fn downgrade(&self) -> WeakSender<M> {
         WeakSender {
             inner: RefCounter::downgrade(&self.inner),
+            high: self.high.as_ref().map(RefCounter::downgrade),
+            policy: self.policy,
+            evict: self.evict.clone(),
+            overflow: self.overflow.clone(),
         }
     }
 
+    // Send with Block policy would wait for the channel to have space before the timeout.
+    // Other policies never wait.
     pub(crate) async fn send_timeout(&self, msg: M, dur: Duration) -> Result<(), ActixSendError> {
-        let fut = self.send(msg);
-        crate::util::runtime::timeout(dur, fut)
-            .await?
-            .map_err(|_| ActixSendError::Closed)?;
-        Ok(())
+        match self.policy {
+            MailboxPolicy::Block => {
+                let fut = self.send(msg);
+                crate::util::runtime::timeout(dur, fut)
+                    .await?
+                    .map_err(|_| ActixSendError::Closed)?;
+                Ok(())
+            }
+            _ => self.try_send_with_policy(msg).map_err(|e| self.reject(e)),
+        }
+    }
+
+    // Send a control message waiting for the channel to have space before the timeout.
+    // Control messages are never dropped by the policy of mailbox.
+    pub(crate) async fn send_control(&self, msg: M, dur: Duration) -> Result<(), ActixSendError> {
+        crate::util::runtime::timeout(dur, self.send(msg)).await?
+    }
+
+    // The high priority lane. Fall back to the normal lane if there is no high priority lane.
+    pub(crate) fn high_lane(&self) -> &Sender<M> {
+        self.high.as_deref().unwrap_or(self)
+    }
+
+    pub(crate) fn with_high_lane(mut self, high: Sender<M>) -> Self {
+        self.high = Some(RefCounter::new(high));
+        self
+    }
+
+    pub(crate) fn with_overflow(mut self, overflow: Overflow<M>) -> Self {
+        self.overflow = Some(RefCounter::new(overflow));
+        self
+    }
+
+    // Send a message following the policy of mailbox without a timeout.
+    pub(crate) async fn send_with_policy(&self, msg: M) -> Result<(), ActixSendError> {
+        self.deliver(msg).await.map_err(|e| self.reject(e))
+    }
+
+    // Convert the error of sending to ActixSendError. A message dropped for the full mailbox is
+    // rejected to it's caller or the dead letter sink.
+    pub(crate) fn reject(&self, e: TrySendError<M>) -> ActixSendError {
+        match e {
+            TrySendError::Full(msg) => {
+                self.drop_full(msg);
+                ActixSendError::Full
+            }
+            e => e.into(),
+        }
+    }
+
+    fn drop_full(&self, msg: M) {
+        if let Some(overflow) = self.overflow.as_ref() {
+            (overflow.reject)(msg);
+        }
+    }
+
+    // Send a message following the policy of mailbox without a timeout.
+    // The message is given back if it's not delivered.
+    pub(crate) async fn deliver(&self, msg: M) -> Result<(), TrySendError<M>> {
+        match self.policy {
+            MailboxPolicy::Block => self.send_back(msg).await.map_err(TrySendError::Closed),
+            _ => self.try_send_with_policy(msg),
+        }
+    }
+
+    // Try to send a message following the policy of mailbox.
+    //
+    // The message is given back in TrySendError::Full when the channel is full and the policy is
+    // not DropOldest. DropOldest would give it back when there is no message can be evicted.
+    pub(crate) fn try_send_with_policy(&self, mut msg: M) -> Result<(), TrySendError<M>> {
+        loop {
+            match self.inner.try_send(msg) {
+                Ok(()) => return Ok(()),
+                // drop the oldest message in channel and try again.
+                Err(TrySendError::Full(m)) if self.policy == MailboxPolicy::DropOldest => {
+                    if !self.evict_oldest() {
+                        return Err(TrySendError::Full(m));
+                    }
+                    msg = m;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    // The number of messages in the normal and high priority lanes of channel.
+    pub(crate) fn len(&self) -> usize {
+        self.inner.len() + self.high.as_ref().map_or(0, |high| high.len())
+    }
+
+    pub(crate) fn is_blocking(&self) -> bool {
+        self.policy == MailboxPolicy::Block
+    }
+
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn with_policy(
+        mut self,
+        policy: MailboxPolicy,
+        rx: &AsyncChannelReceiver<M>,
+    ) -> Self {
+        if policy == MailboxPolicy::DropOldest {
+            self.evict = Some(RefCounter::new(rx.clone()));
+        }
+        self.policy = policy;
+        self
+    }
+
+    // mpsc receiver can not be shared with the sender so we fall back to DropNewest.
+    #[cfg(feature = "actix-runtime-mpsc")]
+    pub(crate) fn with_policy(
+        mut self,
+        policy: MailboxPolicy,
+        _rx: &AsyncChannelReceiver<M>,
+    ) -> Self {
+        self.policy = match policy {
+            MailboxPolicy::DropOldest => MailboxPolicy::DropNewest,
+            policy => policy,
+        };
+        self
+    }
+
+    // Evict the oldest message in channel. Return false if there is nothing can be evicted.
+    //
+    // Control messages are only sent to the lanes never evicting so they are not dropped or
+    // reordered here.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    fn evict_oldest(&self) -> bool {
+        match self.evict.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            Some(msg) => {
+                self.drop_full(msg);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[cfg(feature = "actix-runtime-mpsc")]
+    fn evict_oldest(&self) -> bool {
+        false
     }
 }
 
-#[cfg(not(any(feature = "actix-runtime-mpsc")))]
 impl<M> Sender<M>
 where
     M: 'static,
 {
     pub(crate) async fn send(&self, msg: M) -> Result<(), ActixSendError> {
-        self.inner
-            .send(msg)
+        self.send_back(msg)
             .await
             .map_err(|_| ActixSendError::Closed)
     }
 
-    pub(crate) fn try_send(&self, msg: M) -> Result<(), ActixSendError> {
-        self.inner.try_send(msg).map_err(|_| ActixSendError::Closed)
+    // Send a message and give it back if the channel is closed.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) async fn send_back(&self, msg: M) -> Result<(), M> {
+        self.inner.send(msg).await.map_err(|e| e.0)
     }
-}
 
-#[cfg(any(feature = "actix-runtime-mpsc"))]
-impl<M> Sender<M>
-where
-    M: 'static,
-{
-    pub(crate) async fn send(&self, msg: M) -> Result<(), ActixSendError> {
-        self.inner.send(msg).map_err(|_| ActixSendError::Closed)
+    #[cfg(feature = "actix-runtime-mpsc")]
+    pub(crate) async fn send_back(&self, msg: M) -> Result<(), M> {
+        self.inner.send(msg).await
     }
+}
 
-    pub(crate) fn try_send(&self, msg: M) -> Result<(), ActixSendError> {
-        self.inner.send(msg).map_err(|_| ActixSendError::Closed)
+impl<M> From<TrySendError<M>> for ActixSendError {
+    fn from(e: TrySendError<M>) -> Self {
+        match e {
+            TrySendError::Full(_) => ActixSendError::Full,
+            TrySendError::Closed(_) => ActixSendError::Closed,
+        }
     }
 }
 
 pub struct WeakSender<M> {
     inner: WeakRefCounter<AsyncChannelSender<M>>,
+    high: Option<WeakRefCounter<Sender<M>>>,
+    policy: MailboxPolicy,
+    evict: Option<RefCounter<AsyncChannelReceiver<M>>>,
+    overflow: Option<RefCounter<Overflow<M>>>,
 }
 
 impl<M> Clone for WeakSender<M> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            high: self.high.clone(),
+            policy: self.policy,
+            evict: self.evict.clone(),
+            overflow: self.overflow.clone(),
         }
     }
 }
 
 impl<M> WeakSender<M> {
     pub(crate) fn upgrade(&self) -> Option<Sender<M>> {
-        WeakRefCounter::upgrade(&self.inner).map(|inner| Sender { inner })
+        let high = match self.high.as_ref() {
+            Some(high) => Some(WeakRefCounter::upgrade(high)?),
+            None => None,
+        };
+
+        WeakRefCounter::upgrade(&self.inner).map(|inner| Sender {
+            inner,
+            high,
+            policy: self.policy,
+            evict: self.evict.clone(),
+            overflow: self.overflow.clone(),
+        })
     }
 }
 
-// ToDo: for now there is no way to remove closed actor instance.
+// The senders of every actor instance's own mailbox paired with the id of actor instance.
+// Senders are added when scaling up actor instances and removed when actor instances exit.
+type Senders<A> = Lock<Vec<(usize, Sender<ContextMessage<A>>)>>;
+
 pub struct GroupSender<A>
 where
     A: Actor,
 {
-    inner: RefCounter<Vec<Sender<ContextMessage<A>>>>,
+    inner: RefCounter<Senders<A>>,
 }
 
 impl<A> From<Vec<Sender<ContextMessage<A>>>> for GroupSender<A>
@@ -113,7 +302,7 @@ where
 {
     fn from(sender: Vec<Sender<ContextMessage<A>>>) -> Self {
         Self {
-            inner: RefCounter::new(sender),
+            inner: RefCounter::new(Lock::new(sender.into_iter().enumerate().collect())),
         }
     }
 }
@@ -131,7 +320,7 @@ where
 
 impl<A> GroupSender<A>
 where
-    A: Actor,
+    A: Actor + 'static,
 {
     pub(crate) fn downgrade(&self) -> WeakGroupSender<A> {
         WeakGroupSender {
@@ -139,8 +328,66 @@ where
         }
     }
 
-    pub(crate) fn as_slice(&self) -> &[Sender<ContextMessage<A>>] {
-        self.inner.as_slice()
+    // the sender of actor instance with the given id.
+    pub(crate) fn get(&self, id: usize) -> Option<Sender<ContextMessage<A>>> {
+        self.inner
+            .lock()
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, tx)| tx.clone())
+    }
+
+    pub(crate) fn senders(&self) -> Vec<Sender<ContextMessage<A>>> {
+        self.inner.lock().iter().map(|(_, tx)| tx.clone()).collect()
+    }
+
+    // the ids of actor instances that are not closed.
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn ids(&self) -> Vec<usize> {
+        self.inner
+            .lock()
+            .iter()
+            .filter(|(_, tx)| !tx.is_closed())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // the number of actor instances that are not closed and the messages in their mailbox.
+    pub(crate) fn load(&self) -> (usize, usize) {
+        self.inner
+            .lock()
+            .iter()
+            .filter(|(_, tx)| !tx.is_closed())
+            .fold((0, 0), |(num, len), (_, tx)| (num + 1, len + tx.len()))
+    }
+
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn push(&self, id: usize, tx: Sender<ContextMessage<A>>) {
+        self.inner.lock().push((id, tx));
+    }
+
+    pub(crate) fn remove(&self, id: usize) {
+        self.inner.lock().retain(|(i, _)| *i != id);
+    }
+
+    #[cfg(not(feature = "actix-runtime-mpsc"))]
+    pub(crate) fn remove_closed(&self) {
+        self.inner.lock().retain(|(_, tx)| !tx.is_closed());
+    }
+
+    // pick the sender of actor instance with the router.
+    pub(crate) fn route(
+        &self,
+        router: Router,
+        key: Option<u64>,
+        next: &AtomicUsize,
+    ) -> Option<Sender<ContextMessage<A>>> {
+        // work stealing never touch the lock.
+        if router == Router::WorkStealing {
+            return None;
+        }
+
+        route(router, self.inner.lock().as_slice(), key, next).cloned()
     }
 }
 
@@ -148,7 +395,7 @@ pub struct WeakGroupSender<A>
 where
     A: Actor,
 {
-    inner: WeakRefCounter<Vec<Sender<ContextMessage<A>>>>,
+    inner: WeakRefCounter<Senders<A>>,
 }
 
 impl<A> Clone for WeakGroupSender<A>
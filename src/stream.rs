@@ -11,7 +11,7 @@ use crate::address::MapResult;
 use crate::context::{ContextMessage, InstantMessage};
 use crate::error::ActixSendError;
 use crate::sender::Sender;
-use crate::util::channel::{oneshot_channel, OneShotReceiver};
+use crate::util::channel::{oneshot_channel, OneShotReceiver, TrySendError};
 
 #[pin_project]
 pub struct ActorStream<A, S, I, M>
@@ -28,9 +28,90 @@ where
     _m: PhantomData<M>,
 }
 
-enum ActorStreamState<A> {
+enum ActorStreamState<R> {
     Next,
-    Last(OneShotReceiver<A>),
+    // The mailbox is full and we are waiting for it to have space.
+    Sending(SendFuture, OneShotReceiver<Result<R, ActixSendError>>),
+    Last(OneShotReceiver<Result<R, ActixSendError>>),
+}
+
+type SendFuture = Pin<Box<dyn Future<Output = Result<(), ActixSendError>> + Send>>;
+
+type PollResult<T> = Poll<Option<Result<T, ActixSendError>>>;
+
+// Push a message to actor's mailbox following it's policy.
+// When the mailbox is full and the policy is Block we return a future that wait for the space.
+fn push<A>(
+    tx: &Sender<ContextMessage<A>>,
+    msg: ContextMessage<A>,
+) -> Result<Option<SendFuture>, ActixSendError>
+where
+    A: Actor + 'static,
+{
+    match tx.try_send_with_policy(msg) {
+        Ok(()) => Ok(None),
+        Err(TrySendError::Full(msg)) if tx.is_blocking() => {
+            let tx = tx.clone();
+            Ok(Some(Box::pin(async move { tx.send(msg).await })))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Poll the state of stream until the result of last message is resolved.
+//
+// Return None if we are ready to take the next item from stream.
+fn poll_state<R, T>(
+    state: &mut ActorStreamState<R>,
+    cx: &mut Context<'_>,
+    map: impl FnOnce(R) -> Result<T, ActixSendError>,
+) -> Option<PollResult<T>> {
+    loop {
+        match core::mem::replace(state, ActorStreamState::Next) {
+            ActorStreamState::Next => return None,
+            ActorStreamState::Sending(mut fut, rx) => match fut.as_mut().poll(cx) {
+                Poll::Ready(Ok(())) => *state = ActorStreamState::Last(rx),
+                Poll::Ready(Err(e)) => return Some(Poll::Ready(Some(Err(e)))),
+                Poll::Pending => {
+                    *state = ActorStreamState::Sending(fut, rx);
+                    return Some(Poll::Pending);
+                }
+            },
+            ActorStreamState::Last(mut rx) => {
+                return match Pin::new(&mut rx).poll(cx) {
+                    Poll::Ready(Ok(res)) => Some(Poll::Ready(Some(res.and_then(map)))),
+                    Poll::Ready(Err(_)) => Some(Poll::Ready(Some(Err(ActixSendError::Canceled)))),
+                    Poll::Pending => {
+                        *state = ActorStreamState::Last(rx);
+                        Some(Poll::Pending)
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Send a stream item to actor and poll for the result.
+fn poll_item<A, M>(
+    state: &mut ActorStreamState<A::Result>,
+    tx: &Sender<ContextMessage<A>>,
+    item: M,
+    cx: &mut Context<'_>,
+) -> PollResult<<M as MapResult<A::Result>>::Output>
+where
+    A: Actor + 'static,
+    M: Into<A::Message> + MapResult<A::Result>,
+{
+    let (tx_one, rx) = oneshot_channel();
+    let msg = ContextMessage::instant(InstantMessage::Static(Some(tx_one), item.into()));
+
+    *state = match push(tx, msg) {
+        Ok(Some(fut)) => ActorStreamState::Sending(fut, rx),
+        Ok(None) => ActorStreamState::Last(rx),
+        Err(e) => return Poll::Ready(Some(Err(e))),
+    };
+
+    poll_state(state, cx, M::map).unwrap_or(Poll::Pending)
 }
 
 impl<A, S, I, M> ActorStream<A, S, I, M>
@@ -62,44 +143,14 @@ where
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.project();
 
-        match this.state {
-            ActorStreamState::Next => match this.stream.poll_next(cx) {
-                Poll::Ready(Some(item)) => {
-                    let (tx, mut rx) = oneshot_channel();
-                    let msg = ContextMessage::Instant(InstantMessage::Static(
-                        Some(tx),
-                        item.into().into(),
-                    ));
-
-                    if let Err(e) = this.tx.try_send(msg) {
-                        return Poll::Ready(Some(Err(e)));
-                    }
+        if let Some(poll) = poll_state(this.state, cx, M::map) {
+            return poll;
+        }
 
-                    match Pin::new(&mut rx).poll(cx) {
-                        Poll::Ready(res) => match res {
-                            Ok(res) => Poll::Ready(Some(M::map(res))),
-                            Err(_) => Poll::Ready(Some(Err(ActixSendError::Canceled))),
-                        },
-                        Poll::Pending => {
-                            *this.state = ActorStreamState::Last(rx);
-                            Poll::Pending
-                        }
-                    }
-                }
-                Poll::Pending => Poll::Pending,
-                Poll::Ready(None) => Poll::Ready(None),
-            },
-            ActorStreamState::Last(ref mut rx) => match Pin::new(rx).poll(cx) {
-                Poll::Ready(res) => {
-                    let poll = match res {
-                        Ok(res) => Poll::Ready(Some(M::map(res))),
-                        Err(_) => Poll::Ready(Some(Err(ActixSendError::Canceled))),
-                    };
-                    *this.state = ActorStreamState::Next;
-                    poll
-                }
-                Poll::Pending => Poll::Pending,
-            },
+        match this.stream.poll_next(cx) {
+            Poll::Ready(Some(item)) => poll_item::<A, M>(this.state, this.tx, item.into(), cx),
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(None) => Poll::Ready(None),
         }
     }
 }
@@ -115,7 +166,7 @@ where
     #[pin]
     stream: S,
     tx: Sender<ContextMessage<A>>,
-    rx_one: Option<OneShotReceiver<A::Result>>,
+    state: ActorStreamState<A::Result>,
     _m: PhantomData<M>,
 }
 
@@ -130,7 +181,7 @@ where
         Self {
             stream,
             tx,
-            rx_one: None,
+            state: ActorStreamState::Next,
             _m: PhantomData,
         }
     }
@@ -148,18 +199,9 @@ where
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let mut this = self.project();
 
-        // if we have a one shot receiver then we are waiting for the last message result.
-        if let Some(ref mut rx) = this.rx_one.as_mut() {
-            return match Pin::new(rx).poll(cx) {
-                Poll::Pending => Poll::Pending,
-                Poll::Ready(res) => {
-                    *this.rx_one = None;
-                    match res {
-                        Ok(res) => Poll::Ready(Some(M::map(res))),
-                        Err(_) => Poll::Ready(Some(Err(ActixSendError::Canceled))),
-                    }
-                }
-            };
+        // if we have a pending message then we are waiting for the last message result.
+        if let Some(poll) = poll_state(this.state, cx, M::map) {
+            return poll;
         }
 
         // poll and handle a new stream item.
@@ -169,26 +211,7 @@ where
                 Poll::Ready(None) => return Poll::Ready(None),
                 Poll::Ready(Some(None)) => continue,
                 Poll::Ready(Some(Some(item))) => {
-                    let (tx, mut rx) = oneshot_channel::<<A as Actor>::Result>();
-                    let msg = ContextMessage::Instant(InstantMessage::Static(
-                        Some(tx),
-                        item.into().into(),
-                    ));
-
-                    if let Err(e) = this.tx.try_send(msg) {
-                        return Poll::Ready(Some(Err(e)));
-                    }
-
-                    return match Pin::new(&mut rx).poll(cx) {
-                        Poll::Pending => {
-                            *this.rx_one = Some(rx);
-                            Poll::Pending
-                        }
-                        Poll::Ready(res) => match res {
-                            Ok(res) => Poll::Ready(Some(M::map(res))),
-                            Err(_) => Poll::Ready(Some(Err(ActixSendError::Canceled))),
-                        },
-                    };
+                    return poll_item::<A, M>(this.state, this.tx, item.into(), cx)
                 }
             }
         }
@@ -3,10 +3,12 @@ use core::marker::PhantomData;
 use core::pin::Pin;
 use core::time::Duration;
 
+use std::sync::Arc;
 use std::thread::JoinHandle;
 
 use crate::actor::Actor;
 use crate::context::{ContextMessage, InstantMessage};
+use crate::dead_letter::{DeadLetter, DeadLetterReason, DeadLetterSink};
 use crate::error::ActixSendError;
 use crate::object::AnyObjectContainer;
 use crate::sender::WeakSender;
@@ -19,6 +21,7 @@ use crate::util::{
 // Sender<ContextMessage<Actor>> and an associate message type.
 macro_rules! subscribe {
     ($($send:ident)*) => {
+        #[derive(Default)]
         pub(crate) struct Subscribe {
             inner: RefCounter<AsyncLock<Vec<Box<dyn SubscribeTrait $( + $send)*>>>>,
         }
@@ -54,7 +57,13 @@ macro_rules! subscribe {
                 Box::pin(async move {
                     // We downcast message trait object to the Message type of WeakSender.
 
-                    let msg = msg.unpack::<M>()?;
+                    let msg = match msg.unpack::<M>() {
+                        Some(msg) => msg,
+                        None => {
+                            self.dead_letter(msg, DeadLetterReason::TypeCast);
+                            return None;
+                        }
+                    };
                     let res = self._send(msg, timeout).await;
                     Some(res)
                 })
@@ -65,14 +74,6 @@ macro_rules! subscribe {
 
 subscribe!(Send);
 
-impl Default for Subscribe {
-    fn default() -> Self {
-        Self {
-            inner: Default::default(),
-        }
-    }
-}
-
 impl Clone for Subscribe {
     fn clone(&self) -> Self {
         Self {
@@ -82,13 +83,17 @@ impl Clone for Subscribe {
 }
 
 impl Subscribe {
-    pub(crate) async fn push<A, M>(&self, sender: WeakSender<ContextMessage<A>>)
-    where
+    pub(crate) async fn push<A, M>(
+        &self,
+        sender: WeakSender<ContextMessage<A>>,
+        dead_letters: Option<Arc<dyn DeadLetterSink>>,
+    ) where
         A: Actor + 'static,
         M: Send + Into<A::Message> + 'static,
     {
         self.lock().await.push(Box::new(Subscriber {
             sender,
+            dead_letters,
             _message: PhantomData::<JoinHandle<M>>,
         }));
     }
@@ -100,6 +105,8 @@ where
     M: Send + 'static,
 {
     sender: WeakSender<ContextMessage<A>>,
+    // the dead letter sink of subscriber.
+    dead_letters: Option<Arc<dyn DeadLetterSink>>,
     _message: PhantomData<JoinHandle<M>>,
 }
 
@@ -109,16 +116,31 @@ where
     M: Send + Into<A::Message> + 'static,
 {
     async fn _send(&self, msg: M, timeout: Duration) -> Result<(), ActixSendError> {
-        let sender = self.sender.upgrade().ok_or(ActixSendError::Closed)?;
-        let f = sender.send(ContextMessage::Instant(InstantMessage::Static(
-            None,
-            msg.into(),
-        )));
+        let msg: A::Message = msg.into();
+
+        let sender = match self.sender.upgrade() {
+            Some(sender) => sender,
+            None => {
+                self.dead_letter(AnyObjectContainer::pack(msg), DeadLetterReason::Closed);
+                return Err(ActixSendError::Closed);
+            }
+        };
 
-        runtime::timeout(timeout, f)
-            .await?
-            .map_err(|_| ActixSendError::Closed)?;
+        let f = sender.send_back(ContextMessage::instant(InstantMessage::Static(None, msg)));
+
+        if let Err(msg) = runtime::timeout(timeout, f).await? {
+            if let Some(msg) = msg.into_message() {
+                self.dead_letter(AnyObjectContainer::pack(msg), DeadLetterReason::Closed);
+            }
+            return Err(ActixSendError::Closed);
+        }
 
         Ok(())
     }
+
+    fn dead_letter(&self, msg: AnyObjectContainer, reason: DeadLetterReason) {
+        if let Some(sink) = self.dead_letters.as_ref() {
+            sink.dead_letter(DeadLetter::new(core::any::type_name::<A>(), reason, msg));
+        }
+    }
 }
@@ -0,0 +1,575 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use futures_util::future::{pending, select, select_all, Either};
+use tokio::sync::{broadcast, watch};
+
+use crate::actor::{Actor, Handler};
+use crate::address::{Address, StopMode};
+use crate::builder::{Builder, Config};
+use crate::context::ActorContextState;
+use crate::util::{
+    channel::{oneshot_channel, OneShotReceiver, OneShotSender},
+    runtime,
+    smart_pointer::{Lock, RefCounter},
+};
+
+/// The strategy of restarting actor instances of one address when one of them fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SupervisorStrategy {
+    /// Only restart the failed actor instance.
+    OneForOne,
+    /// Restart all actor instances of the address.
+    OneForAll,
+    /// Restart the failed actor instance and the ones started after it.(Instances with larger id)
+    RestForOne,
+}
+
+// A signal from supervisor to all actor contexts of one address.
+#[derive(Clone, Copy)]
+pub(crate) enum Signal {
+    // restart the actor instances with id >= from except the failed one.
+    Restart { from: usize, except: usize },
+    // all actor instances should stop. Messages left in mailbox would be handled before the
+    // deadline and rejected after it.
+    Stop(Option<Instant>),
+}
+
+// signals kept for an actor context busy with a message. A lagging actor context would restart.
+const SIGNAL_CAPACITY: usize = 64;
+
+// Restart bookkeeping shared by all actor contexts of one address.
+pub(crate) struct Supervision {
+    strategy: SupervisorStrategy,
+    intensity: RestartIntensity,
+    escalated: AtomicBool,
+    // the address is shut down by a failure instead of a deliberate stop.
+    failed: AtomicBool,
+    // every actor context receives all signals in order so restarts are not coalesced.
+    signal: broadcast::Sender<Signal>,
+    // the deadline of stop. It's kept for actor contexts lagging behind or subscribed after it.
+    stop: Lock<Option<Option<Instant>>>,
+    // final states of actor instances after shutdown by supervisor.
+    reports: Lock<Vec<ActorContextState>>,
+    // notify when all actor instances have reported.
+    reported: (watch::Sender<bool>, watch::Receiver<bool>),
+}
+
+impl Supervision {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            strategy: config.supervisor_strategy,
+            intensity: RestartIntensity::new(config.max_restarts, config.restart_backoff),
+            escalated: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
+            signal: broadcast::channel(SIGNAL_CAPACITY).0,
+            stop: Lock::new(None),
+            reports: Lock::new(Vec::new()),
+            reported: watch::channel(false),
+        }
+    }
+
+    // record a restart and return the backoff before the restart.
+    // return None if the restart intensity is exceeded and the address should shut down.
+    pub(crate) fn record_restart(&self) -> Option<Duration> {
+        if self.escalated.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let delay = self.intensity.record();
+        if delay.is_none() {
+            self.fail();
+            self.escalated.store(true, Ordering::Release);
+        }
+        delay
+    }
+
+    // mark the address as failed. A supervisor would restart a failed child after it's shut down.
+    pub(crate) fn fail(&self) {
+        self.failed.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::Acquire)
+    }
+
+    // notify other actor instances to restart according to the strategy.
+    pub(crate) fn notify_restart(&self, id: usize) {
+        let from = match self.strategy {
+            SupervisorStrategy::OneForOne => return,
+            SupervisorStrategy::OneForAll => 0,
+            SupervisorStrategy::RestForOne => id + 1,
+        };
+
+        if !self.escalated.load(Ordering::Acquire) {
+            let _ = self.signal.send(Signal::Restart { from, except: id });
+        }
+    }
+
+    // notify all actor instances to stop. No restart would happen after this.
+    pub(crate) fn notify_stop(&self, deadline: Option<Instant>) {
+        // a stop before escalation is deliberate and the address has not failed.
+        if !self.escalated.swap(true, Ordering::AcqRel) {
+            self.failed.store(false, Ordering::Release);
+        }
+        self.stop.lock().get_or_insert(deadline);
+        let _ = self.signal.send(Signal::Stop(deadline));
+    }
+
+    // the deadline of stop if all actor instances should stop.
+    pub(crate) fn stopped(&self) -> Option<Option<Instant>> {
+        *self.stop.lock()
+    }
+
+    // a new receiver only receives the signals sent after it so an actor instance spawned after a
+    // restart would not act on it again.
+    pub(crate) fn signal_receiver(&self) -> broadcast::Receiver<Signal> {
+        self.signal.subscribe()
+    }
+
+    // push the final state of an actor instance. `last` would be called with the lock held and
+    // it returns true when it's the last actor instance and the report is published.
+    pub(crate) fn report<F>(&self, state: ActorContextState, last: F) -> bool
+    where
+        F: FnOnce() -> bool,
+    {
+        let mut reports = self.reports.lock();
+        reports.push(state);
+
+        let last = last();
+        if last {
+            let _ = self.reported.0.send(true);
+        }
+        last
+    }
+
+    // publish the report when there is no actor context alive to report. Every actor context
+    // holds a receiver of signal until it's dropped.
+    pub(crate) fn report_if_idle(&self) {
+        let _reports = self.reports.lock();
+        if self.signal.receiver_count() == 0 {
+            let _ = self.reported.0.send(true);
+        }
+    }
+
+    pub(crate) async fn wait_report(&self) -> Vec<ActorContextState> {
+        let mut rx = self.reported.1.clone();
+        while !*rx.borrow() {
+            if rx.changed().await.is_err() {
+                break;
+            }
+        }
+        self.reports.lock().clone()
+    }
+}
+
+// The max restarts within a duration and the exponential backoff between restarts.
+pub(crate) struct RestartIntensity {
+    max_restarts: Option<(usize, Duration)>,
+    backoff: Option<(Duration, Duration)>,
+    // timestamps of recent restarts.
+    restarts: Lock<VecDeque<Instant>>,
+}
+
+// without a max_restarts window we only keep enough restarts for the backoff.
+const MAX_RECORD: usize = 32;
+
+impl RestartIntensity {
+    pub(crate) fn new(
+        max_restarts: Option<(usize, Duration)>,
+        backoff: Option<(Duration, Duration)>,
+    ) -> Self {
+        Self {
+            max_restarts,
+            backoff,
+            restarts: Lock::new(VecDeque::new()),
+        }
+    }
+
+    // record a restart and return the backoff before the restart.
+    // return None if the restart intensity is exceeded.
+    pub(crate) fn record(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let mut restarts = self.restarts.lock();
+
+        match self.max_restarts {
+            Some((max, within)) => {
+                while restarts
+                    .front()
+                    .map(|t| now.duration_since(*t) > within)
+                    .unwrap_or(false)
+                {
+                    restarts.pop_front();
+                }
+
+                if restarts.len() >= max {
+                    return None;
+                }
+            }
+            None => {
+                if restarts.len() == MAX_RECORD {
+                    restarts.pop_front();
+                }
+            }
+        }
+
+        restarts.push_back(now);
+
+        let delay = self.backoff.map(|(min, max)| {
+            let exp = (restarts.len() - 1).min(31) as u32;
+            min.checked_mul(1 << exp).unwrap_or(max).min(max)
+        });
+
+        Some(delay.unwrap_or_default())
+    }
+}
+
+/// A supervisor owns a set of child actor addresses and restarts them when they are shut down
+/// by exceeding their own `Builder::max_restarts` or by a panic.
+///
+/// *. A child stopped by `Address::stop` stays stopped and `Child::address` would return None.
+///
+/// Children are started in the order they are added. `SupervisorStrategy` decides which children
+/// are restarted along with the failed one. `SupervisorStrategy::RestForOne` would restart the
+/// children added after it.
+///
+/// # Example:
+/// ```rust
+/// use actix_send::prelude::*;
+/// use actix_send::Supervisor;
+///
+/// #[actor(no_static)]
+/// pub struct MyActor;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut supervisor = Supervisor::new().strategy(SupervisorStrategy::OneForAll);
+///
+///     let child = supervisor.child(MyActor::builder(|| async { MyActor }));
+///
+///     let _handle = supervisor.start().await;
+///
+///     let address: Address<MyActor> = child.address().unwrap();
+/// }
+/// ```
+pub struct Supervisor {
+    strategy: SupervisorStrategy,
+    max_restarts: Option<(usize, Duration)>,
+    backoff: Option<(Duration, Duration)>,
+    children: Vec<BoxedChild>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            strategy: SupervisorStrategy::OneForOne,
+            max_restarts: None,
+            backoff: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Set the strategy of restarting children when one of them is shut down.
+    ///
+    /// Default is `SupervisorStrategy::OneForOne`
+    pub fn strategy(mut self, strategy: SupervisorStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Allow at most `max` restarts of children within the given duration.
+    ///
+    /// When the limit is exceeded all children would be shut down and
+    /// `SupervisorHandle::shutdown_report` would resolve with their final states.
+    ///
+    /// Default is no limit.
+    pub fn max_restarts(mut self, max: usize, within: Duration) -> Self {
+        self.max_restarts = Some((max, within));
+        self
+    }
+
+    /// Wait before restarting children. The wait starts from `min` and doubles on every restart
+    /// until it reaches `max`.
+    ///
+    /// Default is no backoff.
+    pub fn restart_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.backoff = Some((min, max));
+        self
+    }
+
+    /// Add a child actor builder and return a typed handle to it's address.
+    pub fn child<A>(&mut self, builder: Builder<A>) -> Child<A>
+    where
+        A: Actor + Handler + 'static,
+    {
+        let child = Child {
+            address: RefCounter::new(Lock::new(None)),
+        };
+
+        self.children.push(Box::new(ChildSlot {
+            builder,
+            child: child.clone(),
+        }));
+
+        child
+    }
+
+    /// Start all children in order and supervise them.
+    ///
+    /// *. Dropping the returned `SupervisorHandle` would shut down all children.
+    pub async fn start(self) -> SupervisorHandle {
+        for child in self.children.iter() {
+            child.start().await;
+        }
+
+        let (tx, rx) = oneshot_channel();
+        let reports = RefCounter::new(Lock::new(None));
+        let reported = watch::channel(false);
+
+        let tree = SupervisorTree {
+            strategy: self.strategy,
+            intensity: RestartIntensity::new(self.max_restarts, self.backoff),
+            children: self.children,
+            reports: reports.clone(),
+            reported: reported.0,
+        };
+
+        runtime::spawn(tree.run(rx));
+
+        SupervisorHandle {
+            _tx: tx,
+            reports,
+            reported: reported.1,
+        }
+    }
+}
+
+/// A typed handle to the address of a supervised child.
+pub struct Child<A>
+where
+    A: Actor + 'static,
+{
+    address: RefCounter<Lock<Option<Address<A>>>>,
+}
+
+impl<A> Clone for Child<A>
+where
+    A: Actor,
+{
+    fn clone(&self) -> Self {
+        Self {
+            address: self.address.clone(),
+        }
+    }
+}
+
+impl<A> Child<A>
+where
+    A: Actor,
+{
+    /// The current address of the child. The address would change when the child is restarted.
+    ///
+    /// Return None when the child is not started or shut down.
+    pub fn address(&self) -> Option<Address<A>> {
+        self.address.lock().clone()
+    }
+}
+
+/// A handle of a running supervisor.
+pub struct SupervisorHandle {
+    // notify the supervisor to shut down all children when dropped.
+    _tx: OneShotSender<()>,
+    reports: RefCounter<Lock<Option<Vec<Vec<ActorContextState>>>>>,
+    reported: watch::Receiver<bool>,
+}
+
+impl SupervisorHandle {
+    /// Wait for the supervisor shut down all children when `Supervisor::max_restarts` is exceeded.
+    ///
+    /// Would return the final states of actor contexts for every child in the order they are
+    /// added.
+    pub async fn shutdown_report(&self) -> Vec<Vec<ActorContextState>> {
+        let mut rx = self.reported.clone();
+        while !*rx.borrow() {
+            if rx.changed().await.is_err() {
+                break;
+            }
+        }
+        self.reports.lock().clone().unwrap_or_default()
+    }
+}
+
+struct SupervisorTree {
+    strategy: SupervisorStrategy,
+    intensity: RestartIntensity,
+    children: Vec<BoxedChild>,
+    reports: RefCounter<Lock<Option<Vec<Vec<ActorContextState>>>>>,
+    reported: watch::Sender<bool>,
+}
+
+impl SupervisorTree {
+    async fn run(self, mut rx: OneShotReceiver<()>) {
+        // select_all would panic on empty children.
+        if self.children.is_empty() {
+            let _ = rx.await;
+            return;
+        }
+
+        loop {
+            let wait = select_all(self.children.iter().map(|child| child.wait()));
+
+            let idx = match select(wait, &mut rx).await {
+                Either::Left(((_, idx, _), _)) => idx,
+                // handle is dropped.
+                Either::Right(_) => break,
+            };
+
+            match self.intensity.record() {
+                Some(delay) => {
+                    if delay > Duration::from_secs(0) {
+                        runtime::delay_for(delay).await;
+                    }
+                    self.restart(idx).await;
+                }
+                None => break,
+            }
+        }
+
+        let mut reports = Vec::with_capacity(self.children.len());
+        for child in self.children.iter().rev() {
+            reports.push(child.stop().await);
+        }
+        reports.reverse();
+
+        *self.reports.lock() = Some(reports);
+        let _ = self.reported.send(true);
+    }
+
+    async fn restart(&self, idx: usize) {
+        let range = match self.strategy {
+            SupervisorStrategy::OneForOne => idx..idx + 1,
+            SupervisorStrategy::OneForAll => 0..self.children.len(),
+            SupervisorStrategy::RestForOne => idx..self.children.len(),
+        };
+
+        // children stopped deliberately stay stopped.
+        let stopped = range
+            .clone()
+            .map(|i| i != idx && self.children[i].is_stopped())
+            .collect::<Vec<_>>();
+
+        // stop children in the reverse order they started.
+        for i in range.clone().rev() {
+            if i != idx {
+                let _ = self.children[i].stop().await;
+            }
+        }
+
+        for (i, stopped) in range.zip(stopped) {
+            if !stopped {
+                self.children[i].start().await;
+            }
+        }
+    }
+}
+
+// A type erased child of supervisor.
+struct ChildSlot<A>
+where
+    A: Actor + 'static,
+{
+    builder: Builder<A>,
+    child: Child<A>,
+}
+
+macro_rules! supervised_child {
+    ($($send:ident)*) => {
+        // children are borrowed by the futures of supervisor so they must be Sync if the
+        // futures are Send.
+        type BoxedChild = Box<dyn SupervisedChild $( + $send + Sync)*>;
+
+        type BoxedFuture<'a, T> = Pin<Box<dyn Future<Output = T> $( + $send)* + 'a>>;
+
+        trait SupervisedChild {
+            // start a new address for the child.
+            fn start(&self) -> BoxedFuture<'_, ()>;
+
+            // wait for the child shut down by it's own supervision.
+            fn wait(&self) -> BoxedFuture<'_, Vec<ActorContextState>>;
+
+            // shut down the child and wait for it's final states.
+            fn stop(&self) -> BoxedFuture<'_, Vec<ActorContextState>>;
+
+            // check if the child is stopped deliberately by `Address::stop`.
+            fn is_stopped(&self) -> bool;
+        }
+    };
+}
+
+#[cfg(not(any(feature = "actix-runtime", feature = "actix-runtime-mpsc")))]
+supervised_child!(Send);
+
+#[cfg(any(feature = "actix-runtime", feature = "actix-runtime-mpsc"))]
+supervised_child!();
+
+impl<A> SupervisedChild for ChildSlot<A>
+where
+    A: Actor + Handler + 'static,
+{
+    fn start(&self) -> BoxedFuture<'_, ()> {
+        Box::pin(async move {
+            let address = self.builder.clone().start().await;
+            *self.child.address.lock() = Some(address);
+        })
+    }
+
+    fn wait(&self) -> BoxedFuture<'_, Vec<ActorContextState>> {
+        let address = self.child.address();
+        Box::pin(async move {
+            let address = match address {
+                Some(address) => address,
+                None => return pending().await,
+            };
+
+            let report = address.shutdown_report().await;
+            if address.state().supervision().has_failed() {
+                return report;
+            }
+
+            // the child is stopped deliberately and stays stopped.
+            self.child.address.lock().take();
+            pending().await
+        })
+    }
+
+    fn is_stopped(&self) -> bool {
+        match self.child.address() {
+            Some(address) => {
+                let supervision = address.state().supervision();
+                supervision.stopped().is_some() && !supervision.has_failed()
+            }
+            None => true,
+        }
+    }
+
+    fn stop(&self) -> BoxedFuture<'_, Vec<ActorContextState>> {
+        let address = self.child.address.lock().take();
+        Box::pin(async move {
+            match address {
+                Some(address) => address.stop(StopMode::Immediate).await,
+                None => Vec::new(),
+            }
+        })
+    }
+}
@@ -1,10 +1,11 @@
 pub(crate) use channel_inner::{
     bounded, oneshot_channel, unbounded, OneShotReceiver, OneShotSender, Receiver, Sender,
+    TrySendError,
 };
 
 #[cfg(not(any(feature = "actix-runtime-mpsc")))]
 pub(crate) mod channel_inner {
-    pub(crate) use async_channel::{Receiver, Sender};
+    pub(crate) use async_channel::{Receiver, Sender, TrySendError};
     pub(crate) use tokio::sync::oneshot::{
         channel as oneshot_channel, Receiver as OneShotReceiver, Sender as OneShotSender,
     };
@@ -20,18 +21,127 @@ pub(crate) mod channel_inner {
 
 #[cfg(feature = "actix-runtime-mpsc")]
 pub(crate) mod channel_inner {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::{Context, Poll};
+
+    use std::sync::Arc;
+
+    use tokio::sync::mpsc;
     pub(crate) use tokio::sync::{
-        mpsc::{UnboundedReceiver as Receiver, UnboundedSender as Sender},
+        mpsc::error::TrySendError,
         oneshot::{
             channel as oneshot_channel, Receiver as OneShotReceiver, Sender as OneShotSender,
         },
     };
 
-    pub(crate) fn bounded<A>(_cap: usize) -> (Sender<A>, Receiver<A>) {
-        tokio::sync::mpsc::unbounded_channel()
+    // tokio use different types for bounded and unbounded mpsc channel.
+    // We wrap them in enums so the rest of the crate can treat them the same.
+    // mpsc channel does not expose it's length so we count the messages in it.
+    pub(crate) struct Sender<A> {
+        inner: SenderInner<A>,
+        len: Arc<AtomicUsize>,
+    }
+
+    pub(crate) struct Receiver<A> {
+        inner: ReceiverInner<A>,
+        len: Arc<AtomicUsize>,
+    }
+
+    enum SenderInner<A> {
+        Bounded(mpsc::Sender<A>),
+        Unbounded(mpsc::UnboundedSender<A>),
+    }
+
+    enum ReceiverInner<A> {
+        Bounded(mpsc::Receiver<A>),
+        Unbounded(mpsc::UnboundedReceiver<A>),
+    }
+
+    impl<A> Sender<A> {
+        pub(crate) async fn send(&self, msg: A) -> Result<(), A> {
+            match &self.inner {
+                // wait for the capacity before counting the message. A send future dropped while
+                // waiting would not leave a count behind.
+                SenderInner::Bounded(tx) => match tx.reserve().await {
+                    Ok(permit) => {
+                        // count the message before sending so the receiver never sees a message
+                        // not counted.
+                        self.len.fetch_add(1, Ordering::SeqCst);
+                        permit.send(msg);
+                        Ok(())
+                    }
+                    Err(_) => Err(msg),
+                },
+                SenderInner::Unbounded(tx) => {
+                    self.len.fetch_add(1, Ordering::SeqCst);
+                    tx.send(msg).map_err(|e| {
+                        self.len.fetch_sub(1, Ordering::SeqCst);
+                        e.0
+                    })
+                }
+            }
+        }
+
+        pub(crate) fn try_send(&self, msg: A) -> Result<(), TrySendError<A>> {
+            self.len.fetch_add(1, Ordering::SeqCst);
+            let res = match &self.inner {
+                SenderInner::Bounded(tx) => tx.try_send(msg),
+                SenderInner::Unbounded(tx) => tx.send(msg).map_err(|e| TrySendError::Closed(e.0)),
+            };
+            if res.is_err() {
+                self.len.fetch_sub(1, Ordering::SeqCst);
+            }
+            res
+        }
+
+        pub(crate) fn is_closed(&self) -> bool {
+            match &self.inner {
+                SenderInner::Bounded(tx) => tx.is_closed(),
+                SenderInner::Unbounded(tx) => tx.is_closed(),
+            }
+        }
+
+        pub(crate) fn len(&self) -> usize {
+            self.len.load(Ordering::SeqCst)
+        }
+    }
+
+    impl<A> Receiver<A> {
+        pub(crate) fn close(&mut self) {
+            match &mut self.inner {
+                ReceiverInner::Bounded(rx) => rx.close(),
+                ReceiverInner::Unbounded(rx) => rx.close(),
+            }
+        }
+
+        pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<A>> {
+            let res = match &mut self.inner {
+                ReceiverInner::Bounded(rx) => rx.poll_recv(cx),
+                ReceiverInner::Unbounded(rx) => rx.poll_recv(cx),
+            };
+            if let Poll::Ready(Some(_)) = res {
+                self.len.fetch_sub(1, Ordering::SeqCst);
+            }
+            res
+        }
+    }
+
+    pub(crate) fn bounded<A>(cap: usize) -> (Sender<A>, Receiver<A>) {
+        let (tx, rx) = mpsc::channel(cap);
+        pair(SenderInner::Bounded(tx), ReceiverInner::Bounded(rx))
     }
 
     pub(crate) fn unbounded<A>() -> (Sender<A>, Receiver<A>) {
-        tokio::sync::mpsc::unbounded_channel()
+        let (tx, rx) = mpsc::unbounded_channel();
+        pair(SenderInner::Unbounded(tx), ReceiverInner::Unbounded(rx))
+    }
+
+    fn pair<A>(tx: SenderInner<A>, rx: ReceiverInner<A>) -> (Sender<A>, Receiver<A>) {
+        let len = Arc::new(AtomicUsize::new(0));
+        let tx = Sender {
+            inner: tx,
+            len: len.clone(),
+        };
+        (tx, Receiver { inner: rx, len })
     }
 }
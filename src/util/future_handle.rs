@@ -39,8 +39,11 @@ macro_rules! spawn_cancel {
                 tx: None,
             };
 
-            runtime::spawn(async {
+            let finished = handler.waker.clone();
+            runtime::spawn(async move {
                 let either = future.await;
+                // mark the future as finished.
+                finished.lock().0 = true;
                 on_ready(either).await;
             });
 
@@ -118,6 +121,7 @@ where
                 let index = *index;
                 runtime::spawn(async move {
                     let _ = tx
+                        .high_lane()
                         .send(ContextMessage::Interval(IntervalMessage::Remove(index)))
                         .await;
                 });
@@ -125,6 +129,11 @@ where
         }
     }
 
+    // return true if the future is finished or canceled.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.waker.lock().0
+    }
+
     pub(crate) fn attach_tx(&mut self, index: usize, tx: WeakSender<ContextMessage<A>>) {
         self.tx = Some((index, tx));
     }
@@ -2,3 +2,4 @@ pub(crate) mod channel;
 pub(crate) mod future_handle;
 pub(crate) mod runtime;
 pub(crate) mod smart_pointer;
+pub(crate) mod span;
@@ -129,6 +129,98 @@ where
     panic!("spawn_blocking does not work for actix-runtime.\r\nPlease use web::block directly in your handle method");
 }
 
+// check if the current thread is polling futures of the async runtime where blocking would stall
+// the runtime.
+pub(crate) fn in_runtime() -> bool {
+    #[cfg(feature = "tokio-runtime")]
+    #[cfg(not(any(
+        feature = "async-std-runtime",
+        feature = "actix-runtime",
+        feature = "actix-runtime-mpsc"
+    )))]
+    {
+        tokio::runtime::Handle::try_current().is_ok() && is_budgeted()
+    }
+
+    #[cfg(feature = "async-std-runtime")]
+    #[cfg(not(any(
+        feature = "tokio-runtime",
+        feature = "actix-runtime",
+        feature = "actix-runtime-mpsc"
+    )))]
+    {
+        async_std::task::try_current().is_some()
+    }
+
+    #[cfg(any(feature = "actix-runtime", feature = "actix-runtime-mpsc"))]
+    #[cfg(not(any(feature = "async-std-runtime", feature = "tokio-runtime")))]
+    {
+        actix_rt::Arbiter::try_current().is_some()
+    }
+}
+
+// tokio budgets the futures polled by the workers and block_on of runtime. The threads of
+// spawn_blocking and the closures of block_in_place are unconstrained so they can block.
+#[cfg(feature = "tokio-runtime")]
+#[cfg(not(any(
+    feature = "async-std-runtime",
+    feature = "actix-runtime",
+    feature = "actix-runtime-mpsc"
+)))]
+fn is_budgeted() -> bool {
+    let waker = futures_util::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    // a constrained budget is exhausted before the bound. The budget of tokio is 128.
+    let mut restores = Vec::new();
+    let budgeted = loop {
+        match tokio::task::coop::poll_proceed(&mut cx) {
+            Poll::Ready(restore) => restores.push(restore),
+            Poll::Pending => break true,
+        }
+        if restores.len() > u8::MAX as usize {
+            break false;
+        }
+    };
+
+    // every restore sets the budget before it's taken so the first one is dropped last.
+    while let Some(restore) = restores.pop() {
+        drop(restore);
+    }
+
+    budgeted
+}
+
+// run a future to completion on the current thread by parking it until the future is woken.
+// It works on all runtime as long as the future does not rely on the timer or IO of a runtime.
+// ActixSendError::Timeout would return if the future is not finished in the given duration.
+pub(crate) fn block_on<F: Future>(dur: Duration, f: F) -> Result<F::Output, ActixSendError> {
+    struct ThreadWaker(std::thread::Thread);
+
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = std::sync::Arc::new(ThreadWaker(std::thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+
+    let deadline = std::time::Instant::now() + dur;
+    let mut f = Box::pin(f);
+    loop {
+        if let Poll::Ready(res) = f.as_mut().poll(&mut cx) {
+            return Ok(res);
+        }
+        let now = std::time::Instant::now();
+        if now >= deadline {
+            return Err(ActixSendError::Timeout);
+        }
+        // park could wake up spuriously so the future is polled again.
+        std::thread::park_timeout(deadline - now);
+    }
+}
+
 // from tokio::task::yield_now(). give control back to scheduler.
 // We copy/paste this so we can use it on all runtime.
 pub(crate) async fn yield_now() {
@@ -0,0 +1,45 @@
+use core::future::Future;
+
+// The span of caller is carried with message so actor would handle it under the caller's span.
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+// A placeholder of span when tracing feature is not enabled.
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn current() -> Self {
+        Span
+    }
+
+    pub(crate) fn none() -> Self {
+        Span
+    }
+}
+
+// Handle a message in a child span of the caller's span.
+#[cfg(feature = "tracing")]
+pub(crate) fn instrument<F>(
+    fut: F,
+    parent: &Span,
+    actor: &'static str,
+    message: &'static str,
+    id: usize,
+) -> impl Future<Output = F::Output>
+where
+    F: Future,
+{
+    let span = tracing::info_span!(parent: parent, "handle", actor, message, id);
+    tracing::Instrument::instrument(fut, span)
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn instrument<F>(fut: F, _: &Span, _: &'static str, _: &'static str, _: usize) -> F
+where
+    F: Future,
+{
+    fut
+}
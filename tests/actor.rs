@@ -1,7 +1,9 @@
 use core::time::Duration;
+use std::time::Instant;
 
 use actix_send::prelude::*;
 use actix_send::Builder;
+use futures_util::FutureExt;
 
 use crate::my_actor::*;
 
@@ -13,9 +15,10 @@ pub mod my_actor {
     pub struct TestActor {
         pub state1: String,
         pub state2: String,
+        pub handled: usize,
     }
 
-    #[message(result = "u8")]
+    #[message(result = "u8", always_run)]
     pub struct DummyMessage1 {
         pub from: String,
     }
@@ -23,6 +26,9 @@ pub mod my_actor {
     #[message(result = "u16")]
     pub struct DummyMessage2(pub u32, pub usize);
 
+    #[message(result = "Result<u8, std::io::Error>", fallible)]
+    pub struct DummyMessage3;
+
     #[handler]
     impl Handler for TestActor {
         async fn handle(&mut self, msg123: DummyMessage1) -> u8 {
@@ -36,9 +42,106 @@ pub mod my_actor {
     impl Handler for TestActor {
         async fn handle(&mut self, _: DummyMessage2) -> u16 {
             assert_eq!("running2", self.state2);
+            self.handled += 1;
             16
         }
     }
+
+    #[message(result = "usize", route_key)]
+    pub struct DummyMessage6(pub usize);
+
+    impl RouteKey for DummyMessage6 {
+        type Key = usize;
+
+        fn route_key(&self) -> usize {
+            self.0
+        }
+    }
+
+    #[handler]
+    impl Handler for TestActor {
+        async fn handle(&mut self, _: DummyMessage6, ctx: &mut Context<Self>) -> usize {
+            ctx.id()
+        }
+    }
+
+    #[message(result = "u64")]
+    pub struct DummyMessage7;
+
+    #[handler]
+    impl Handler for TestActor {
+        async fn handle(&mut self, _: DummyMessage7, ctx: &mut Context<Self>) -> u64 {
+            let envelope = ctx.envelope();
+            if let Some(sender) = envelope.sender() {
+                assert_eq!(sender, "test");
+                assert_eq!(envelope.header("key"), Some("value"));
+            }
+            envelope.correlation_id()
+        }
+    }
+
+    #[message(result = "Result<usize, std::io::Error>", fallible, idempotent)]
+    pub struct DummyMessage8(pub std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    // attributes of message are moved to the message enum so we implement Clone manually.
+    impl Clone for DummyMessage8 {
+        fn clone(&self) -> Self {
+            DummyMessage8(self.0.clone())
+        }
+    }
+
+    impl Idempotent for DummyMessage8 {}
+
+    #[handler]
+    impl Handler for TestActor {
+        async fn handle(&mut self, msg: DummyMessage8) -> Result<usize, std::io::Error> {
+            // fail until the third attempt.
+            match msg.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1 {
+                3 => Ok(3),
+                _ => Err(std::io::Error::other("transient")),
+            }
+        }
+    }
+
+    #[message(result = "usize", priority = "high")]
+    pub struct DummyMessage5;
+
+    #[handler]
+    impl Handler for TestActor {
+        async fn handle(&mut self, _: DummyMessage5) -> usize {
+            self.handled
+        }
+    }
+
+    #[message(result = "usize")]
+    pub struct DummyMessage4 {
+        pub guard: std::sync::Arc<()>,
+        pub stop: bool,
+    }
+
+    #[handler]
+    impl Handler for TestActor {
+        async fn handle(&mut self, msg: DummyMessage4, ctx: &mut Context<Self>) -> usize {
+            let guard = msg.guard;
+            ctx.spawn(async move {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                drop(guard);
+            });
+            if msg.stop {
+                ctx.stop();
+            }
+            ctx.id()
+        }
+    }
+
+    #[handler]
+    impl Handler for TestActor {
+        async fn handle(&mut self, _: DummyMessage3) -> Result<u8, std::io::Error> {
+            // corrupt the state before return error.
+            self.state1 = String::from("failed");
+            Err(std::io::Error::other("failed"))
+        }
+    }
 }
 
 #[tokio::test]
@@ -68,6 +171,28 @@ async fn weak_addr() {
     assert!(weak.upgrade().is_none());
 }
 
+#[tokio::test]
+async fn drop_address() {
+    let address = test_actor_builder().start().await;
+
+    // the future holds a sender that is dropped when the delay is canceled.
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    address
+        .run_later(Duration::from_secs(60), move |_| {
+            let _ = &tx;
+            async {}.boxed()
+        })
+        .await
+        .unwrap();
+    // the delayed future is scheduled once the next message is handled.
+    let _ = address.send(DummyMessage2(1, 2)).await;
+
+    // dropping the last address shuts down actor(s) and cancels the delay.
+    drop(address);
+    let res = tokio::time::timeout(Duration::from_secs(5), rx).await;
+    assert!(matches!(res, Ok(Err(_))));
+}
+
 #[tokio::test]
 async fn active_count() {
     let address = test_actor_builder().num(8).start().await;
@@ -83,11 +208,1363 @@ async fn active_count() {
     assert_eq!(address.current_active(), 5);
 }
 
+#[tokio::test]
+async fn mailbox_fail_fast() {
+    let address = test_actor_builder()
+        .mailbox_capacity(1)
+        .mailbox_policy(MailboxPolicy::FailFast)
+        .start()
+        .await;
+
+    // keep the actor busy so the mailbox can fill up.
+    let addr = address.clone();
+    tokio::spawn(async move {
+        let _ = addr
+            .run(|_| tokio::time::sleep(Duration::from_millis(500)).boxed())
+            .await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    address.do_send(DummyMessage2(1, 2));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let res = address.send(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Full)));
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let res = address.send(DummyMessage2(1, 2)).await;
+    assert_eq!(res.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn mailbox_drop_oldest_closed() {
+    let address = test_actor_builder()
+        .num(1)
+        .mailbox_capacity(1)
+        .mailbox_policy(MailboxPolicy::DropOldest)
+        .start()
+        .await;
+
+    // the only actor instance is stopped by a panic.
+    let res = address
+        .run(|_| async { panic!("panic in actor") }.boxed())
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Panicked)));
+
+    // the mailbox is closed even when senders hold a receiver for evicting messages.
+    let res = address.send(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Closed)));
+}
+
+#[tokio::test]
+async fn mailbox_drop_oldest_control() {
+    let address = test_actor_builder()
+        .metrics()
+        .mailbox_capacity(1)
+        .mailbox_policy(MailboxPolicy::DropOldest)
+        .start()
+        .await;
+
+    // keep the actor busy so the high priority lane can fill up.
+    address.do_run(|_| tokio::time::sleep(Duration::from_millis(100)).boxed());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // the delayed message is not evicted by the high priority message.
+    address
+        .send_later(DummyMessage2(1, 2), Duration::from_millis(10))
+        .await
+        .unwrap();
+    let res = address.send(DummyMessage5).await;
+    assert!(matches!(res, Err(ActixSendError::Full)));
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let metrics = address.metrics();
+    assert_eq!(metrics.message("DummyMessage2").unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn mailbox_drop_policy() {
+    use std::sync::{Arc, Mutex};
+
+    let letters = Arc::new(Mutex::new(Vec::new()));
+    let letters_clone = letters.clone();
+
+    let address = test_actor_builder()
+        .metrics()
+        .mailbox_capacity(2)
+        .mailbox_policy(MailboxPolicy::DropOldest)
+        .dead_letters(move |letter: DeadLetter| letters_clone.lock().unwrap().push(letter.reason()))
+        .start()
+        .await;
+
+    // keep the actor busy so the mailbox can fill up.
+    let addr = address.clone();
+    tokio::spawn(async move {
+        let _ = addr
+            .run(|_| tokio::time::sleep(Duration::from_millis(300)).boxed())
+            .await;
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // delayed message is a control message and never evicted.
+    address
+        .send_later(DummyMessage2(1, 2), Duration::from_millis(10))
+        .await
+        .unwrap();
+    let addr = address.clone();
+    let evicted = tokio::spawn(async move { addr.send(DummyMessage2(1, 2)).await });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    address.do_send(DummyMessage2(1, 2));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // the caller of evicted message gets the error.
+    address.do_send(DummyMessage2(1, 2));
+    let res = evicted.await.unwrap();
+    assert!(matches!(res, Err(ActixSendError::Full)));
+
+    // evicted message without a caller goes to dead letters.
+    address.do_send(DummyMessage2(1, 2));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(*letters.lock().unwrap(), vec![DeadLetterReason::Full]);
+
+    // the delayed message and the last two messages are handled.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let metrics = address.metrics();
+    assert_eq!(metrics.message("DummyMessage2").unwrap().count(), 3);
+
+    let letters = Arc::new(Mutex::new(Vec::new()));
+    let letters_clone = letters.clone();
+
+    let address = test_actor_builder()
+        .mailbox_capacity(1)
+        .mailbox_policy(MailboxPolicy::DropNewest)
+        .dead_letters(move |letter: DeadLetter| letters_clone.lock().unwrap().push(letter.reason()))
+        .start()
+        .await;
+
+    let addr = address.clone();
+    tokio::spawn(async move {
+        let _ = addr
+            .run(|_| tokio::time::sleep(Duration::from_millis(300)).boxed())
+            .await;
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    address.do_send(DummyMessage2(1, 2));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // the message being sent is dropped.
+    let res = address.send(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Full)));
+    address.do_send(DummyMessage2(1, 2));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(*letters.lock().unwrap(), vec![DeadLetterReason::Full]);
+}
+
+#[tokio::test]
+async fn restart_on_err() {
+    let address = test_actor_builder().restart_on_err().start().await;
+
+    match address.send(DummyMessage3).await {
+        Ok(Err(e)) => assert_eq!(e.to_string(), "failed"),
+        _ => panic!("DummyMessage3 must return a handler error"),
+    }
+
+    // actor is rebuilt and the corrupted state is gone.
+    let msg = DummyMessage1 {
+        from: "a simple test".to_string(),
+    };
+    assert_eq!(address.send(msg).await.unwrap(), 8);
+}
+
+#[tokio::test]
+async fn panic_isolation() {
+    let address = test_actor_builder().num(2).start().await;
+
+    let _ = tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(address.current_active(), 2);
+
+    let res = address
+        .run(|_| async { panic!("panic in actor") }.boxed())
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Panicked)));
+
+    // the panicked actor is stopped and the rest are still working.
+    let _ = tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(address.current_active(), 1);
+    assert_eq!(address.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+
+    let address = test_actor_builder().restart_on_err().start().await;
+
+    let res = address
+        .run(|_| async { panic!("panic in actor") }.boxed())
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Panicked)));
+
+    // the panicked actor is rebuilt.
+    assert_eq!(address.current_active(), 1);
+    assert_eq!(address.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn restart_panic() {
+    let builds = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let builds2 = builds.clone();
+    let address = TestActor::builder(move || {
+        let builds = builds2.clone();
+        async move {
+            // the builder panics on every restart.
+            if builds.fetch_add(1, std::sync::atomic::Ordering::SeqCst) > 0 {
+                panic!("panic in builder");
+            }
+            TestActor {
+                state1: String::from("running1"),
+                state2: String::from("running2"),
+                handled: 0,
+            }
+        }
+    })
+    .restart_on_err()
+    .max_restarts(2, Duration::from_secs(10))
+    .start()
+    .await;
+
+    assert!(address.send(DummyMessage3).await.unwrap().is_err());
+
+    // every failed restart counts against max restarts and the address is shut down.
+    let report = tokio::time::timeout(Duration::from_secs(1), address.shutdown_report())
+        .await
+        .unwrap();
+
+    assert_eq!(report.len(), 1);
+    assert_eq!(builds.load(std::sync::atomic::Ordering::SeqCst), 3);
+    assert_eq!(address.current_active(), 0);
+}
+
+#[tokio::test]
+async fn supervisor_strategy() {
+    let address = test_actor_builder()
+        .num(2)
+        .supervisor_strategy(SupervisorStrategy::OneForAll)
+        .max_restarts(1, Duration::from_secs(10))
+        .start()
+        .await;
+
+    let _ = tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // the first failure restarts all actor instances.
+    assert!(address.send(DummyMessage3).await.unwrap().is_err());
+    let _ = tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(address.current_active(), 2);
+
+    // the second failure exceeds max restarts and shuts down the address.
+    assert!(address.send(DummyMessage3).await.unwrap().is_err());
+
+    let report = tokio::time::timeout(Duration::from_secs(1), address.shutdown_report())
+        .await
+        .unwrap();
+
+    assert_eq!(report.len(), 2);
+    assert!(report.iter().all(|state| state.generation() == 1));
+    assert_eq!(address.current_active(), 0);
+
+    let res = address.send(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Closed)));
+}
+
+#[tokio::test]
+async fn supervisor_signal_new_instance() {
+    let address = test_actor_builder()
+        .supervisor_strategy(SupervisorStrategy::OneForAll)
+        .start()
+        .await;
+
+    // the failure signals all actor instances to restart.
+    assert!(address.send(DummyMessage3).await.unwrap().is_err());
+
+    // an actor instance added after the signal would not restart on it.
+    let ids = address.add_instances(1).await.unwrap();
+    let res = address.send_to(ids[0], DummyMessage2(1, 2)).await;
+    assert_eq!(res.unwrap(), 16);
+
+    let state = address
+        .instances()
+        .into_iter()
+        .find(|state| state.id() == ids[0])
+        .unwrap();
+    assert_eq!(state.generation(), 0);
+}
+
+#[tokio::test]
+async fn supervisor_signal_busy_instance() {
+    let address = test_actor_builder()
+        .num(4)
+        .supervisor_strategy(SupervisorStrategy::RestForOne)
+        .start()
+        .await;
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // keep actor instance 2 busy while two restart signals are sent.
+    let addr = address.clone();
+    let busy = tokio::spawn(async move {
+        addr.run_on(2, |_| {
+            async { tokio::time::sleep(Duration::from_millis(300)).await }.boxed()
+        })
+        .await
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // restart instances after 0 and then the ones after 3.
+    assert!(address.send_to(0, DummyMessage3).await.unwrap().is_err());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(address.send_to(3, DummyMessage3).await.unwrap().is_err());
+
+    // the busy instance restarts on the first signal though it's not the latest.
+    busy.await.unwrap().unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let generations = address
+        .instances()
+        .iter()
+        .map(|s| s.generation())
+        .collect::<Vec<_>>();
+    assert_eq!(generations, vec![1, 1, 1, 2]);
+}
+
+#[tokio::test]
+async fn supervisor_tree() {
+    let mut supervisor = Supervisor::new().strategy(SupervisorStrategy::OneForAll);
+
+    let child1 = supervisor.child(
+        test_actor_builder()
+            .restart_on_err()
+            .max_restarts(0, Duration::from_secs(10)),
+    );
+    let child2 = supervisor.child(test_actor_builder());
+
+    let handle = supervisor.start().await;
+
+    let addr1 = child1.address().unwrap();
+    let addr2 = child2.address().unwrap();
+
+    // child1 is shut down and the supervisor restarts all children.
+    assert!(addr1.send(DummyMessage3).await.unwrap().is_err());
+    let _ = tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let res = addr2.send(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Closed)));
+
+    let new_addr1 = child1.address().unwrap();
+    let new_addr2 = child2.address().unwrap();
+    assert_eq!(new_addr1.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+    assert_eq!(new_addr2.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+
+    // dropping the handle shuts down all children.
+    drop(handle);
+    let _ = tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(child1.address().is_none());
+    let res = new_addr1.send(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Closed)));
+}
+
+#[tokio::test]
+async fn supervisor_child_stop() {
+    let mut supervisor = Supervisor::new();
+    let child1 = supervisor.child(test_actor_builder());
+    let child2 = supervisor.child(test_actor_builder());
+    let _handle = supervisor.start().await;
+
+    // a child stopped deliberately is not restarted.
+    let addr1 = child1.address().unwrap();
+    let _ = addr1.stop(StopMode::Drain).await;
+    let _ = tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(child1.address().is_none());
+
+    // a panicked child is restarted.
+    let addr2 = child2.address().unwrap();
+    let res = addr2
+        .run(|_| async { panic!("panic in actor") }.boxed())
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Panicked)));
+    let _ = tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let new_addr2 = child2.address().unwrap();
+    assert_eq!(new_addr2.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn supervisor_child_stop_one_for_all() {
+    let mut supervisor = Supervisor::new().strategy(SupervisorStrategy::OneForAll);
+    let child1 = supervisor.child(test_actor_builder());
+    let child2 = supervisor.child(test_actor_builder());
+    let _handle = supervisor.start().await;
+
+    let addr2 = child2.address().unwrap();
+    let _ = addr2.stop(StopMode::Drain).await;
+    let _ = tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // a failing sibling does not bring the stopped child back.
+    let addr1 = child1.address().unwrap();
+    let res = addr1
+        .run(|_| async { panic!("panic in actor") }.boxed())
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Panicked)));
+    let _ = tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(child2.address().is_none());
+    let new_addr1 = child1.address().unwrap();
+    assert_eq!(new_addr1.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn stop_mode() {
+    async fn stop(num: usize, mode: StopMode) -> Vec<Result<u8, ActixSendError>> {
+        let address = test_actor_builder().num(num).start().await;
+
+        let tasks = (0..4)
+            .map(|_| {
+                let addr = address.clone();
+                tokio::spawn(async move {
+                    addr.run(|_| {
+                        async {
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            1u8
+                        }
+                        .boxed()
+                    })
+                    .await
+                })
+            })
+            .collect::<Vec<_>>();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let report = address.stop(mode).await;
+        assert_eq!(report.len(), num);
+        assert_eq!(address.current_active(), 0);
+
+        let res = address.send(DummyMessage2(1, 2)).await;
+        assert!(matches!(res, Err(ActixSendError::Closed)));
+
+        let mut res = Vec::new();
+        for task in tasks {
+            res.push(task.await.unwrap());
+        }
+        res
+    }
+
+    // all messages in mailbox are handled.
+    let res = stop(2, StopMode::Drain).await;
+    assert!(res.iter().all(|res| matches!(res, Ok(1))));
+
+    // only the message being handled is finished.
+    let res = stop(1, StopMode::Immediate).await;
+    assert_eq!(res.iter().filter(|res| res.is_ok()).count(), 1);
+    assert!(res
+        .iter()
+        .filter(|res| res.is_err())
+        .all(|res| matches!(res, Err(ActixSendError::Closed))));
+}
+
+#[tokio::test]
+async fn stop_exited() {
+    let address = test_actor_builder().start().await;
+
+    let res = address
+        .run(|_| async { panic!("panic in actor") }.boxed())
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Panicked)));
+
+    // the panicked actor instance has exited and there is nothing to wait for.
+    let report = tokio::time::timeout(Duration::from_secs(1), address.stop(StopMode::Drain))
+        .await
+        .unwrap();
+    assert!(report.is_empty());
+    assert_eq!(address.current_active(), 0);
+
+    // stop again after a stop.
+    let address = test_actor_builder().num(2).start().await;
+    assert_eq!(address.stop(StopMode::Drain).await.len(), 2);
+    assert_eq!(address.current_active(), 0);
+    let res = address.send_to(0, DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::InstanceClosed)));
+    let report = tokio::time::timeout(Duration::from_secs(1), address.stop(StopMode::Drain))
+        .await
+        .unwrap();
+    assert_eq!(report.len(), 2);
+}
+
+#[tokio::test]
+async fn context() {
+    let address = test_actor_builder().start().await;
+    let guard = std::sync::Arc::new(());
+
+    let msg = DummyMessage4 {
+        guard: guard.clone(),
+        stop: false,
+    };
+    assert_eq!(address.send(msg).await.unwrap(), 0);
+    assert_eq!(std::sync::Arc::strong_count(&guard), 2);
+
+    // the instance stops and the future it spawned is canceled.
+    let msg = DummyMessage4 {
+        guard: guard.clone(),
+        stop: true,
+    };
+    let _ = address.send(msg).await.unwrap();
+    let _ = tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(address.current_active(), 0);
+    assert_eq!(std::sync::Arc::strong_count(&guard), 1);
+}
+
+#[tokio::test]
+async fn context_detached() {
+    let mut actor = TestActor {
+        state1: String::from("running1"),
+        state2: String::from("running2"),
+        handled: 0,
+    };
+
+    // Handler::handle runs handle methods taking Context with a detached one.
+    let res = Handler::handle(&mut actor, DummyMessage6(3).into()).await;
+    assert_eq!(<DummyMessage6 as MapResult<_>>::map(res).unwrap(), 0);
+
+    let res = Handler::handle(&mut actor, DummyMessage2(1, 2).into()).await;
+    assert_eq!(<DummyMessage2 as MapResult<_>>::map(res).unwrap(), 16);
+}
+
+#[tokio::test]
+async fn send_timeout() {
+    let address = test_actor_builder().start().await;
+
+    // keep the actor busy.
+    let addr = address.clone();
+    tokio::spawn(async move {
+        addr.run(|_| tokio::time::sleep(Duration::from_millis(300)).boxed())
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let res = address
+        .send_timeout(DummyMessage2(1, 2), Duration::from_millis(50))
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Timeout)));
+
+    let res = address
+        .send_timeout(DummyMessage2(1, 2), Duration::from_secs(1))
+        .await;
+    assert_eq!(res.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn cancel_propagation() {
+    let address = test_actor_builder().start().await;
+
+    let addr = address.clone();
+    tokio::spawn(async move {
+        addr.run(|_| tokio::time::sleep(Duration::from_millis(200)).boxed())
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let res = address
+        .send_timeout(DummyMessage2(1, 2), Duration::from_millis(20))
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Timeout)));
+
+    // message marked as always_run is handled after the caller is gone.
+    let msg = DummyMessage1 {
+        from: String::from("a simple test"),
+    };
+    let res = address.send_timeout(msg, Duration::from_millis(20)).await;
+    assert!(matches!(res, Err(ActixSendError::Timeout)));
+
+    assert_eq!(address.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+    assert_eq!(address.skipped_count(), 1);
+}
+
+#[tokio::test]
+async fn priority() {
+    let address = test_actor_builder().start().await;
+
+    let addr = address.clone();
+    tokio::spawn(async move {
+        addr.run(|_| tokio::time::sleep(Duration::from_millis(100)).boxed())
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let tasks = (0..4)
+        .map(|_| {
+            let addr = address.clone();
+            tokio::spawn(async move { addr.send(DummyMessage2(1, 2)).await })
+        })
+        .collect::<Vec<_>>();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // high priority messages are handled before the normal ones in mailbox.
+    assert_eq!(address.send(DummyMessage5).await.unwrap(), 0);
+    let res = address
+        .send_with_priority(DummyMessage5, Priority::Normal)
+        .await;
+    assert_eq!(res.unwrap(), 4);
+
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+}
+
+#[tokio::test]
+async fn priority_bounded() {
+    let address = test_actor_builder()
+        .mailbox_capacity(1)
+        .mailbox_policy(MailboxPolicy::FailFast)
+        .start()
+        .await;
+
+    // keep the actor busy so the high priority lane can fill up.
+    address.do_run(|_| tokio::time::sleep(Duration::from_millis(100)).boxed());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // the high priority lane follows the capacity and policy of mailbox.
+    let fut = address.try_send(DummyMessage5).unwrap();
+    let res = address.try_send(DummyMessage5);
+    assert!(matches!(res, Err(ActixSendError::Full)));
+    let res = address.send(DummyMessage5).await;
+    assert!(matches!(res, Err(ActixSendError::Full)));
+
+    // the normal lane is not affected.
+    let fut2 = address.try_send(DummyMessage2(1, 2)).unwrap();
+    // the high priority message is handled first.
+    assert_eq!(fut.await.unwrap(), 0);
+    assert_eq!(fut2.await.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn router() {
+    let address = test_actor_builder()
+        .num(3)
+        .router(Router::RoundRobin)
+        .start()
+        .await;
+
+    let mut ids = Vec::new();
+    for key in 0..6 {
+        ids.push(address.send(DummyMessage6(key)).await.unwrap());
+    }
+    assert_eq!(ids, vec![0, 1, 2, 0, 1, 2]);
+
+    // messages with the same key go to the same actor instance.
+    let address = test_actor_builder()
+        .num(4)
+        .router(Router::ConsistentHash)
+        .start()
+        .await;
+
+    let mut ids = Vec::new();
+    for key in 0..16 {
+        let id = address.send(DummyMessage6(key)).await.unwrap();
+        assert_eq!(address.send(DummyMessage6(key)).await.unwrap(), id);
+        ids.push(id);
+    }
+    ids.dedup();
+    assert!(ids.len() > 1);
+}
+
+#[tokio::test]
+async fn router_instance_exit() {
+    let address = test_actor_builder()
+        .num(2)
+        .router(Router::RoundRobin)
+        .start()
+        .await;
+
+    // the actor instance panics after messages are queued in it's own mailbox.
+    let addr = address.clone();
+    let task = tokio::spawn(async move {
+        addr.run_on(0, |_| {
+            async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                panic!("panic in actor");
+            }
+            .boxed()
+        })
+        .await
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let tasks = (0..2)
+        .map(|_| {
+            let addr = address.clone();
+            tokio::spawn(async move { addr.send_to(0, DummyMessage2(1, 2)).await })
+        })
+        .collect::<Vec<_>>();
+
+    assert!(matches!(task.await.unwrap(), Err(ActixSendError::Panicked)));
+
+    // the messages left in mailbox of the stopped actor instance are rejected.
+    for task in tasks {
+        let res = task.await.unwrap();
+        assert!(matches!(res, Err(ActixSendError::Closed)));
+    }
+    assert_eq!(address.send(DummyMessage6(0)).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn send_to() {
+    let address = test_actor_builder().num(3).start().await;
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let ids = address
+        .instances()
+        .iter()
+        .map(|s| (s.id(), s.generation()))
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![(0, 0), (1, 0), (2, 0)]);
+
+    for id in 0..3 {
+        assert_eq!(address.send_to(id, DummyMessage6(0)).await.unwrap(), id);
+    }
+    let res = address.run_on(2, |_| async { 2u8 }.boxed()).await;
+    assert_eq!(res.unwrap(), 2);
+
+    let res = address.send_to(3, DummyMessage6(0)).await;
+    assert!(matches!(res, Err(ActixSendError::UnknownInstance)));
+
+    // stop the actor instance of id 1.
+    let msg = DummyMessage4 {
+        guard: std::sync::Arc::new(()),
+        stop: true,
+    };
+    address.send_to(1, msg).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let res = address.send_to(1, DummyMessage6(0)).await;
+    assert!(matches!(res, Err(ActixSendError::InstanceClosed)));
+    assert_eq!(address.instances().len(), 2);
+}
+
+#[tokio::test]
+async fn scale() {
+    let address = test_actor_builder()
+        .num(2)
+        .router(Router::RoundRobin)
+        .start()
+        .await;
+
+    let ids = address.add_instances(2).await.unwrap();
+    assert_eq!(ids, vec![2, 3]);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(address.current_active(), 4);
+
+    let mut ids = Vec::new();
+    for key in 0..4 {
+        ids.push(address.send(DummyMessage6(key)).await.unwrap());
+    }
+    ids.sort_unstable();
+    assert_eq!(ids, vec![0, 1, 2, 3]);
+
+    // the actor instances with the highest ids are closed.
+    let states = address.scale_to(1).await.unwrap();
+    let ids = states.iter().map(|s| s.id()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![3, 2, 1]);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert_eq!(address.current_active(), 1);
+    assert_eq!(address.send(DummyMessage6(0)).await.unwrap(), 0);
+    let res = address.send_to(2, DummyMessage6(0)).await;
+    assert!(matches!(res, Err(ActixSendError::InstanceClosed)));
+
+    assert!(address.scale_to(3).await.unwrap().is_empty());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let ids = address
+        .instances()
+        .iter()
+        .map(|s| s.id())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![0, 4, 5]);
+}
+
+#[tokio::test]
+async fn scale_full_mailbox() {
+    let address = test_actor_builder()
+        .num(2)
+        .router(Router::RoundRobin)
+        .mailbox_capacity(1)
+        .mailbox_policy(MailboxPolicy::FailFast)
+        .start()
+        .await;
+
+    // keep the actor instance of id 1 busy and fill up it's mailbox.
+    let addr = address.clone();
+    tokio::spawn(async move {
+        let _ = addr
+            .run_on(1, |_| tokio::time::sleep(Duration::from_millis(200)).boxed())
+            .await;
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let addr = address.clone();
+    let handle = tokio::spawn(async move { addr.send_to(1, DummyMessage6(0)).await });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let res = address.send_to(1, DummyMessage6(0)).await;
+    assert!(matches!(res, Err(ActixSendError::Full)));
+
+    // the control message is not dropped by the policy and the message in mailbox is handled.
+    let states = address.scale_to(1).await.unwrap();
+    let ids = states.iter().map(|s| s.id()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![1]);
+    assert_eq!(handle.await.unwrap().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn autoscale() {
+    let policy = Autoscale::new(1, 3)
+        .queue_threshold(2)
+        .idle_timeout(Duration::from_millis(100))
+        .interval(Duration::from_millis(20));
+
+    let address = test_actor_builder().autoscale(policy).start().await;
+
+    for _ in 0..30 {
+        address.do_run(|_| tokio::time::sleep(Duration::from_millis(20)).boxed());
+    }
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    assert_eq!(address.current_active(), 3);
+    let events = address.scale_events();
+    assert_eq!((events[0].from(), events[0].to()), (1, 2));
+    assert!(matches!(events[0].reason(), ScaleReason::QueueLength(_)));
+
+    // shrink back to min when idle.
+    tokio::time::sleep(Duration::from_millis(800)).await;
+
+    assert_eq!(address.current_active(), 1);
+    let events = address.scale_events();
+    let last = events.last().unwrap();
+    assert_eq!((last.from(), last.to()), (2, 1));
+    assert_eq!(last.reason(), ScaleReason::Idle);
+}
+
+#[tokio::test]
+async fn metrics() {
+    let address = test_actor_builder()
+        .metrics()
+        .restart_on_err()
+        .start()
+        .await;
+
+    for _ in 0..3 {
+        address.send(DummyMessage2(1, 2)).await.unwrap();
+    }
+    let _ = address.send(DummyMessage3).await;
+    address.run(|_| async {}.boxed()).await.unwrap();
+
+    // the message timed out is skipped.
+    address.do_run(|_| tokio::time::sleep(Duration::from_millis(100)).boxed());
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let res = address
+        .send_timeout(DummyMessage2(1, 2), Duration::from_millis(10))
+        .await;
+    assert!(matches!(res, Err(ActixSendError::Timeout)));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    let metrics = address.metrics();
+    assert_eq!(metrics.queue_len(), 0);
+    assert_eq!(metrics.timeouts(), 1);
+    assert_eq!(metrics.canceled(), 1);
+
+    let instance = &metrics.instances()[0];
+    assert_eq!(instance.generation(), 1);
+    assert_eq!(instance.handled(), 6);
+
+    assert_eq!(metrics.message("DummyMessage2").unwrap().count(), 3);
+    assert_eq!(metrics.message("DummyMessage3").unwrap().count(), 1);
+    let run = metrics.message("run").unwrap();
+    assert_eq!(run.count(), 2);
+    assert!(run.sum() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn envelope() {
+    let address = test_actor_builder().start().await;
+
+    let envelope = Envelope::new()
+        .with_correlation_id(7)
+        .with_sender("test")
+        .with_header("key", "value");
+    assert_eq!(address.send_with(envelope, DummyMessage7).await.unwrap(), 7);
+
+    let id1 = address.send(DummyMessage7).await.unwrap();
+    let id2 = address.send(DummyMessage7).await.unwrap();
+    assert_ne!(id1, id2);
+
+    // the message is skipped after the deadline.
+    address.do_run(|_| tokio::time::sleep(Duration::from_millis(100)).boxed());
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let envelope = Envelope::new().with_deadline(Instant::now() + Duration::from_millis(10));
+    let res = address.send_with(envelope, DummyMessage7).await;
+    assert!(matches!(res, Err(ActixSendError::Timeout)));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    assert_eq!(address.skipped_count(), 1);
+}
+
+#[tokio::test]
+async fn middleware() {
+    use std::sync::{Arc, Mutex};
+
+    // record the calls of middleware hooks.
+    struct Record(&'static str, Arc<Mutex<Vec<String>>>);
+
+    impl Middleware for Record {
+        fn before(&self, info: &MessageInfo<'_>) -> Result<(), ActixSendError> {
+            self.1
+                .lock()
+                .unwrap()
+                .push(format!("{} before {}", self.0, info.message()));
+            match info.envelope().header("deny") {
+                Some(name) if name == self.0 => Err(ActixSendError::Rejected("denied".into())),
+                _ => Ok(()),
+            }
+        }
+
+        fn after(&self, info: &MessageInfo<'_>, outcome: HandleOutcome, _: Duration) {
+            self.1.lock().unwrap().push(format!(
+                "{} after {} {:?}",
+                self.0,
+                info.message(),
+                outcome
+            ));
+        }
+    }
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let address = test_actor_builder()
+        .wrap(Record("outer", calls.clone()))
+        .wrap(Record("inner", calls.clone()))
+        .start()
+        .await;
+
+    let res = address.send(DummyMessage2(1, 2)).await.unwrap();
+    assert_eq!(res, 16);
+    assert_eq!(
+        calls.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        vec![
+            "outer before DummyMessage2",
+            "inner before DummyMessage2",
+            "inner after DummyMessage2 Ok",
+            "outer after DummyMessage2 Ok",
+        ]
+    );
+
+    let res = address.run(|_| async { 1 }.boxed()).await.unwrap();
+    assert_eq!(res, 1);
+    assert_eq!(calls.lock().unwrap().drain(..).count(), 4);
+
+    // rejected by inner middleware.
+    let envelope = Envelope::new().with_header("deny", "inner");
+    let res = address.send_with(envelope, DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Rejected(_))));
+    assert_eq!(
+        calls.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        vec![
+            "outer before DummyMessage2",
+            "inner before DummyMessage2",
+            "outer after DummyMessage2 Rejected",
+        ]
+    );
+
+    let res = address.send(DummyMessage3).await;
+    assert!(matches!(res, Ok(Err(_))));
+    assert_eq!(
+        calls.lock().unwrap().pop().unwrap(),
+        "outer after DummyMessage3 Err"
+    );
+}
+
+#[tokio::test]
+async fn rate_limit() {
+    let address = test_actor_builder()
+        .rate_limit(RateLimit::new(2, Duration::from_millis(100)))
+        .start()
+        .await;
+
+    let fut = address.try_send(DummyMessage2(1, 2)).unwrap();
+    assert_eq!(fut.await.unwrap(), 16);
+    let fut = address.try_send(DummyMessage2(1, 2)).unwrap();
+    assert_eq!(fut.await.unwrap(), 16);
+    let res = address.try_send(DummyMessage2(1, 2));
+    assert!(matches!(res, Err(ActixSendError::RateLimited)));
+
+    // send would wait for the next token.
+    let start = Instant::now();
+    assert_eq!(address.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+    assert!(start.elapsed() >= Duration::from_millis(30));
+
+    // the wait is longer than Builder::timeout.
+    let address = test_actor_builder()
+        .rate_limit(RateLimit::per_second(1).burst(1))
+        .timeout(Duration::from_millis(100))
+        .start()
+        .await;
+
+    assert!(address.send(DummyMessage2(1, 2)).await.is_ok());
+    let res = address.send(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::RateLimited)));
+}
+
+#[tokio::test]
+async fn circuit_breaker() {
+    use std::sync::{Arc, Mutex};
+
+    let transitions = Arc::new(Mutex::new(Vec::new()));
+    let breaker = CircuitBreaker::new(2)
+        .cool_down(Duration::from_millis(50))
+        .on_transition({
+            let transitions = transitions.clone();
+            move |from, to| transitions.lock().unwrap().push((from, to))
+        });
+
+    let address = test_actor_builder().circuit_breaker(breaker).start().await;
+
+    assert_eq!(address.circuit_state(), Some(CircuitState::Closed));
+
+    // DummyMessage3 always returns an error.
+    assert!(address.send(DummyMessage3).await.unwrap().is_err());
+    assert_eq!(address.circuit_state(), Some(CircuitState::Closed));
+    assert!(address.send(DummyMessage3).await.unwrap().is_err());
+    assert_eq!(address.circuit_state(), Some(CircuitState::Open));
+
+    let res = address.send(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::CircuitOpen)));
+
+    // a failed probe opens the circuit again.
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(address.circuit_state(), Some(CircuitState::HalfOpen));
+    assert!(address.send(DummyMessage3).await.unwrap().is_err());
+    assert_eq!(address.circuit_state(), Some(CircuitState::Open));
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(address.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+    assert_eq!(address.circuit_state(), Some(CircuitState::Closed));
+
+    use CircuitState::*;
+    assert_eq!(
+        *transitions.lock().unwrap(),
+        vec![
+            (Closed, Open),
+            (Open, HalfOpen),
+            (HalfOpen, Open),
+            (Open, HalfOpen),
+            (HalfOpen, Closed)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn retry() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let policy = RetryPolicy::new(3)
+        .backoff(Backoff::Fixed(Duration::from_millis(1)))
+        .retry_handler_err();
+
+    let address = test_actor_builder()
+        .metrics()
+        .retry(policy.clone())
+        .start()
+        .await;
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let res = address.send(DummyMessage8(attempts.clone())).await;
+    assert_eq!(res.unwrap().unwrap(), 3);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // not enough attempts.
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let policy = policy.backoff(Backoff::Exponential(
+        Duration::from_millis(1),
+        Duration::from_millis(2),
+    ));
+    let res = address
+        .send_retry(
+            DummyMessage8(attempts.clone()),
+            &RetryPolicy::new(2).retry_handler_err(),
+        )
+        .await;
+    assert!(matches!(res, Ok(Err(_))));
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let res = address
+        .send_retry(DummyMessage8(attempts.clone()), &policy.jitter())
+        .await;
+    assert_eq!(res.unwrap().unwrap(), 3);
+
+    // messages not marked as idempotent are never resent.
+    let res = address.send(DummyMessage3).await;
+    assert!(matches!(res, Ok(Err(_))));
+    let metrics = address.metrics();
+    assert_eq!(metrics.message("DummyMessage3").unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn dead_letters() {
+    use std::sync::{Arc, Mutex};
+
+    let letters = Arc::new(Mutex::new(Vec::new()));
+    let letters_clone = letters.clone();
+
+    let address = test_actor_builder()
+        .dead_letters(move |letter: DeadLetter| {
+            let reason = letter.reason();
+            let msg = letter.downcast::<TestActorMessage>().ok();
+            letters_clone.lock().unwrap().push((reason, msg.is_some()));
+        })
+        .start()
+        .await;
+
+    // pending delayed message is dead lettered when actor stops.
+    address
+        .send_later(DummyMessage2(1, 2), Duration::from_secs(10))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let _ = address.stop(StopMode::Immediate).await;
+
+    // message sent to a stopped address.
+    address.do_send(DummyMessage2(1, 2));
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let letters = letters.lock().unwrap();
+    assert_eq!(
+        *letters,
+        vec![
+            (DeadLetterReason::DelayCanceled, true),
+            (DeadLetterReason::Closed, true)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn send_handle() {
+    let address = test_actor_builder()
+        .mailbox_capacity(1)
+        .mailbox_policy(MailboxPolicy::FailFast)
+        .start()
+        .await;
+
+    assert!(address.do_send_tracked(DummyMessage2(1, 2)).await.is_ok());
+
+    // keep the actor busy so the mailbox can fill up.
+    let handle = address.do_run_tracked(|_| tokio::time::sleep(Duration::from_millis(300)).boxed());
+    assert!(handle.await.is_ok());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(address.do_send_tracked(DummyMessage2(1, 2)).await.is_ok());
+    let res = address.do_send_tracked(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Full)));
+
+    // the handle can be dropped without waiting for the delivery.
+    drop(address.do_send_tracked(DummyMessage2(1, 2)));
+
+    let _ = address.stop(StopMode::Immediate).await;
+    let res = address.do_send_tracked(DummyMessage2(1, 2)).await;
+    assert!(matches!(res, Err(ActixSendError::Closed)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn try_send() {
+    let address = test_actor_builder().mailbox_capacity(1).start().await;
+
+    let fut = address.try_send(DummyMessage2(1, 2)).unwrap();
+    assert_eq!(fut.await.unwrap(), 16);
+
+    // keep the actor busy so the mailbox can fill up.
+    let handle = address.do_run_tracked(|_| tokio::time::sleep(Duration::from_millis(300)).boxed());
+    assert!(handle.await.is_ok());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let fut = address.try_send(DummyMessage2(1, 2)).unwrap();
+    let res = address.try_send(DummyMessage2(1, 2));
+    assert!(matches!(res, Err(ActixSendError::Full)));
+    assert_eq!(fut.await.unwrap(), 16);
+
+    // send from a thread outside of runtime.
+    let addr = address.clone();
+    let res = std::thread::spawn(move || addr.blocking_send(DummyMessage2(1, 2)))
+        .join()
+        .unwrap();
+    assert_eq!(res.unwrap(), 16);
+
+    let _ = address.stop(StopMode::Immediate).await;
+    let res = address.try_send(DummyMessage2(1, 2));
+    assert!(matches!(res, Err(ActixSendError::Closed)));
+    let addr = address.clone();
+    let res = std::thread::spawn(move || addr.blocking_send(DummyMessage2(1, 2)))
+        .join()
+        .unwrap();
+    assert!(matches!(res, Err(ActixSendError::Closed)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn blocking_send() {
+    let address = test_actor_builder()
+        .mailbox_capacity(1)
+        .timeout(Duration::from_millis(50))
+        .start()
+        .await;
+
+    // blocking in async context is refused.
+    let res = address.blocking_send(DummyMessage2(1, 2));
+    assert!(matches!(res, Err(ActixSendError::Blocking)));
+    let addr = address.clone();
+    let res = tokio::spawn(async move { addr.blocking_send(DummyMessage2(1, 2)) })
+        .await
+        .unwrap();
+    assert!(matches!(res, Err(ActixSendError::Blocking)));
+
+    // the threads of spawn_blocking and block_in_place can block.
+    let addr = address.clone();
+    let res = tokio::task::spawn_blocking(move || addr.blocking_send(DummyMessage2(1, 2)))
+        .await
+        .unwrap();
+    assert_eq!(res.unwrap(), 16);
+    let res = tokio::task::block_in_place(|| address.blocking_send(DummyMessage2(1, 2)));
+    assert_eq!(res.unwrap(), 16);
+
+    // keep the actor busy so waiting for the result times out.
+    address.do_run(|_| tokio::time::sleep(Duration::from_millis(300)).boxed());
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let addr = address.clone();
+    let res = std::thread::spawn(move || addr.blocking_send(DummyMessage2(1, 2)))
+        .join()
+        .unwrap();
+    assert!(matches!(res, Err(ActixSendError::Timeout)));
+
+    // the timed out message still holds the only slot of mailbox so the push times out.
+    let addr = address.clone();
+    let res = std::thread::spawn(move || addr.blocking_send(DummyMessage2(1, 2)))
+        .join()
+        .unwrap();
+    assert!(matches!(res, Err(ActixSendError::Timeout)));
+    assert_eq!(address.metrics().timeouts(), 2);
+}
+
+#[cfg(feature = "async-std-runtime")]
+#[async_std::test]
+async fn blocking_send_async_std() {
+    let address = test_actor_builder().start().await;
+
+    let res = address.blocking_send(DummyMessage2(1, 2));
+    assert!(matches!(res, Err(ActixSendError::Blocking)));
+
+    let addr = address.clone();
+    let res =
+        async_std::task::spawn_blocking(move || addr.blocking_send(DummyMessage2(1, 2))).await;
+    assert_eq!(res.unwrap(), 16);
+}
+
+#[cfg(feature = "actix-runtime")]
+#[actix_rt::test]
+async fn blocking_send_actix() {
+    let address = test_actor_builder().start().await;
+
+    let res = address.blocking_send(DummyMessage2(1, 2));
+    assert!(matches!(res, Err(ActixSendError::Blocking)));
+
+    let addr = address.clone();
+    let res = actix_rt::task::spawn_blocking(move || addr.blocking_send(DummyMessage2(1, 2)))
+        .await
+        .unwrap();
+    assert_eq!(res.unwrap(), 16);
+}
+
+#[cfg(feature = "actix-runtime")]
+#[actix_rt::test]
+async fn builder_local_future() {
+    // the future of builder holds a Rc across await so it's not Send.
+    let address = TestActor::builder(|| async {
+        let state = std::rc::Rc::new(String::from("running"));
+        actix_rt::task::yield_now().await;
+
+        TestActor {
+            state1: format!("{}1", state),
+            state2: format!("{}2", state),
+            handled: 0,
+        }
+    })
+    .start()
+    .await;
+
+    assert_eq!(address.send(DummyMessage2(1, 2)).await.unwrap(), 16);
+}
+
+#[tokio::test]
+async fn send_batch() {
+    let address = test_actor_builder().num(2).metrics().start().await;
+
+    let res = address
+        .send_batch((0..3).map(|i| DummyMessage2(i, 2)))
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 3);
+    assert!(res.into_iter().all(|res| matches!(res, Ok(16))));
+
+    // every message has it's own result.
+    let res = address
+        .send_batch(vec![DummyMessage3, DummyMessage3])
+        .await
+        .unwrap();
+    assert!(res.iter().all(|res| matches!(res, Ok(Err(_)))));
+
+    let metrics = address.metrics();
+    assert_eq!(metrics.message("DummyMessage2").unwrap().count(), 3);
+
+    let res = address.send_batch(Vec::<DummyMessage2>::new()).await;
+    assert!(res.unwrap().is_empty());
+
+    let _ = address.stop(StopMode::Immediate).await;
+    let res = address.send_batch(vec![DummyMessage2(1, 2)]).await;
+    assert!(matches!(res, Err(ActixSendError::Closed)));
+}
+
+#[tokio::test]
+async fn send_batch_circuit_breaker() {
+    let breaker = CircuitBreaker::new(2).cool_down(Duration::from_secs(10));
+    let address = test_actor_builder().circuit_breaker(breaker).start().await;
+
+    // a batch with a handler error counts as one failed call.
+    let res = address
+        .send_batch(vec![DummyMessage3, DummyMessage3])
+        .await
+        .unwrap();
+    assert!(res.iter().all(|res| matches!(res, Ok(Err(_)))));
+    assert_eq!(address.circuit_state(), Some(CircuitState::Closed));
+
+    let res = address.send_batch(vec![DummyMessage3]).await.unwrap();
+    assert!(matches!(res[0], Ok(Err(_))));
+    assert_eq!(address.circuit_state(), Some(CircuitState::Open));
+
+    let res = address.send_batch(vec![DummyMessage2(1, 2)]).await;
+    assert!(matches!(res, Err(ActixSendError::CircuitOpen)));
+}
+
 fn test_actor_builder() -> Builder<TestActor> {
     TestActor::builder(|| async {
         let state1 = String::from("running1");
         let state2 = String::from("running2");
 
-        TestActor { state1, state2 }
+        TestActor {
+            state1,
+            state2,
+            handled: 0,
+        }
     })
 }